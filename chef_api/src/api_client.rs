@@ -1,55 +1,433 @@
-use crate::credentials::Config;
+use crate::authentication::signer::Signer;
+use crate::credentials::{self, Config, CredentialsFile, Diagnosis};
+use crate::errors::{ChefError, ServerError, TimeoutKind};
+use crate::http_headers::*;
+use crate::retry::RetryPolicy;
+use crate::utils::{clock_offset, decode_list, encode_query};
 
-use hyper::client::HttpConnector;
-use hyper::Client as HyperClient;
-use hyper_openssl::HttpsConnector;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::{Certificate, Method, NoProxy, Proxy};
 
-use tokio_core::reactor::Core;
+use futures::future;
+use futures::stream::{self, StreamExt};
+use tokio::runtime::{Builder as RuntimeBuilder, Handle, Runtime, RuntimeFlavor};
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tower::{Layer, Service};
 
 use failure::Error;
+use serde::de::DeserializeOwned;
 use serde::ser::*;
 use serde_json::Value;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use crate::requests::*;
 
+/// A boxed future resolving to the response to a request made with `ExecuteAsync`.
+pub type ResponseFuture = Pin<Box<dyn Future<Output = Result<Value, Error>> + Send + 'static>>;
+
+/// A boxed future resolving to whether an object exists, from `ExecuteAsync::exists_async`.
+pub type ExistsFuture = Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'static>>;
+
+/// A boxed future resolving to a response deserialized into a `T`.
+pub type TypedResponseFuture<T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'static>>;
+
+/// Requests without a body are sent, and signed, with an empty JSON string.
+const EMPTY_BODY: &str = "\"\"";
+
+/// The number of requests `fetch_many` makes at once unless told otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The result of fetching each named object with `fetch_many`.
+pub type BulkResults = Vec<(String, Result<Value, Error>)>;
+
+/// The endpoints from which objects can be fetched in bulk with `ApiClient::fetch_many`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BulkEndpoint {
+    /// Fetch nodes by name.
+    Nodes,
+    /// Fetch roles by name.
+    Roles,
+    /// Fetch clients by name.
+    Clients,
+    /// Fetch environments by name.
+    Environments,
+    /// Fetch items from the named data bag.
+    DataBagItems(String),
+}
+
+impl BulkEndpoint {
+    fn request(&self, api: &ApiClient, name: &str) -> ChefRequest {
+        let body = String::from(EMPTY_BODY);
+        match *self {
+            BulkEndpoint::Nodes => api.nodes().node(name).to_request("get", body),
+            BulkEndpoint::Roles => api.roles().role(name).to_request("get", body),
+            BulkEndpoint::Clients => api.clients().client(name).to_request("get", body),
+            BulkEndpoint::Environments => {
+                api.environments().environment(name).to_request("get", body)
+            }
+            BulkEndpoint::DataBagItems(ref bag) => {
+                api.data().data_bag(bag).item(name).to_request("get", body)
+            }
+        }
+    }
+}
+
+/// Limits on how long requests may take. Each is unlimited unless set.
+///
+/// ```rust,no_run
+/// # use chef_api::api_client::{ApiClient, Timeouts};
+/// # use std::time::Duration;
+/// let timeouts = Timeouts {
+///     connect: Some(Duration::from_secs(5)),
+///     total: Some(Duration::from_secs(60)),
+///     ..Timeouts::default()
+/// };
+/// let api = ApiClient::from_credentials(None).unwrap().with_timeouts(timeouts);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timeouts {
+    /// How long to wait when establishing a new connection.
+    pub connect: Option<Duration>,
+    /// How long to wait for the response to start once connected, and then for each part of
+    /// its body.
+    pub read: Option<Duration>,
+    /// How long the whole request may take, including any retries.
+    pub total: Option<Duration>,
+}
+
 /// Struct used to start requests to the Chef Server API.
+///
+/// An `ApiClient` is `Send` and `Sync`, and cheap to clone; clones share the same connection
+/// pool. Requests may be made asynchronously using `ExecuteAsync`, or by blocking the current
+/// thread using `Execute`.
 #[derive(Debug, Clone)]
 pub struct ApiClient {
     /// Configuration for the client, containing credentials
     pub config: Config,
-    /// The Hyper HTTP Client.
-    pub client: Rc<HyperClient<HttpsConnector<HttpConnector>>>,
-    /// The async core
-    pub core: Rc<RefCell<Core>>,
+    /// The HTTP client used for asynchronous requests.
+    pub client: reqwest::Client,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) timeouts: Timeouts,
+    pub(crate) signer: SignerCache,
+    pub(crate) clock: Clock,
+    blocking: Arc<Mutex<Option<Arc<BlockingClient>>>>,
+}
+
+/// Tracks the offset between the local clock and the Chef Server's, as measured from the
+/// `Date` header of each response.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Clock {
+    offset: Arc<Mutex<Option<chrono::Duration>>>,
+    compensate: bool,
 }
 
+impl Clock {
+    fn offset(&self) -> Option<chrono::Duration> {
+        *self.offset.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, headers: &HeaderMap) {
+        if let Some(offset) = clock_offset(headers) {
+            *self.offset.lock().unwrap_or_else(|e| e.into_inner()) = Some(offset);
+        }
+    }
+
+    /// The time with which to sign a request, adjusted by the measured offset if compensation
+    /// is enabled.
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        match self.offset() {
+            Some(offset) if self.compensate => chrono::Utc::now() + offset,
+            _ => chrono::Utc::now(),
+        }
+    }
+}
+
+/// The signer for a client's requests. Unless one was given with `ApiClient::with_signer`, it
+/// is loaded from the client key when first needed and then shared by every request, retry and
+/// clone of the client, so that the key is read, and any passphrase asked for, only once.
+///
+/// `get` may block, and is only called from `ChefRequest::signed_headers`, off the runtime's
+/// own threads.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SignerCache(Arc<Mutex<Option<Arc<dyn Signer>>>>);
+
+impl SignerCache {
+    fn get(&self, config: &Config) -> Result<Arc<dyn Signer>, Error> {
+        let mut signer = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref signer) = *signer {
+            return Ok(Arc::clone(signer));
+        }
+        let loaded: Arc<dyn Signer> = Arc::new(config.signer()?);
+        *signer = Some(Arc::clone(&loaded));
+        Ok(loaded)
+    }
+}
+
+/// The runtime and HTTP client used to service blocking requests. These are kept apart from the
+/// asynchronous client, as pooled connections are tied to the runtime that created them.
+///
+/// Requests run on the runtime's own worker thread, and the caller waits for their result, so
+/// that blocking requests may be made from any thread where blocking is allowed.
+#[derive(Debug)]
+struct BlockingClient {
+    runtime: Option<Runtime>,
+    client: reqwest::Client,
+}
+
+impl BlockingClient {
+    fn new(client: reqwest::Client) -> Result<Self, Error> {
+        let runtime = RuntimeBuilder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("chef-api-blocking")
+            .enable_all()
+            .build()?;
+        Ok(BlockingClient {
+            runtime: Some(runtime),
+            client,
+        })
+    }
+
+    /// Run `future` on the runtime, blocking the current thread until it completes.
+    fn block_on<F>(&self, future: F) -> Result<F::Output, Error>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let runtime = self.runtime.as_ref().ok_or_else(|| {
+            ChefError::BlockingClientError(String::from("the runtime has shut down"))
+        })?;
+        let (tx, rx) = std::sync::mpsc::channel();
+        runtime.spawn(async move {
+            let _ = tx.send(future.await);
+        });
+        let result = match Handle::try_current() {
+            // Lets other tasks on a multi-threaded runtime continue while this thread waits.
+            Ok(ref handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| rx.recv())
+            }
+            _ => rx.recv(),
+        };
+        result.map_err(|_| {
+            ChefError::BlockingClientError(String::from("the request was abandoned")).into()
+        })
+    }
+}
+
+impl Drop for BlockingClient {
+    // The last clone of an `ApiClient` may be dropped inside an async context, where dropping
+    // a runtime would panic.
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// An iterator over a client for each organization on the Chef Server, returned by
+/// `ApiClient::organizations`.
+#[derive(Debug, Clone)]
+pub struct Organizations {
+    api: ApiClient,
+    names: std::vec::IntoIter<String>,
+}
+
+impl Iterator for Organizations {
+    type Item = Result<(String, ApiClient), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        Some(self.api.for_organization(&name).map(|api| (name, api)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+/// The name of the check that the Chef Server authenticates the client.
+const AUTHENTICATION_CHECK: &str = "the Chef Server authenticates the client";
+
 impl ApiClient {
     /// Create a new ApiClient struct. It takes a `Config` type. Typically one would use
     /// `from_credentials` rather than calling this directly.
+    ///
+    /// Returns an error if the `chef_server_url` or signing protocol can't be used.
     pub fn new(config: Config) -> Result<Self, Error> {
-        let core = Core::new()?;
-        let handle = core.handle();
-
-        let client = HyperClient::configure()
-            .connector(HttpsConnector::new(4, &handle)?)
-            .build(&handle);
+        config.url_base()?;
+        config.signing_protocol()?;
+        let client = http_client(&config)?;
 
         Ok(Self {
             config,
-            core: Rc::new(RefCell::new(core)),
-            client: Rc::new(client),
+            client,
+            retry_policy: RetryPolicy::default(),
+            timeouts: Timeouts::default(),
+            signer: SignerCache::default(),
+            clock: Clock::default(),
+            blocking: Arc::new(Mutex::new(None)),
         })
     }
 
     /// Create a new `ApiClient` struct using a set of credentials as defined in Chef RFC 99.
     ///
-    /// Accepts an `Option<String>` containing the name of a credentials profile.
+    /// Accepts an `Option<String>` containing the name of a credentials profile. Environment
+    /// variables may override the profile; see `Config::from_credentials`.
     pub fn from_credentials(profile: Option<&str>) -> Result<Self, Error> {
         Config::from_credentials(profile).map(ApiClient::new)?
     }
 
+    /// Set the policy used to retry failed requests. By default requests are not retried.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Sign requests with `signer`, rather than the client key named in the configuration.
+    ///
+    /// This allows the private key to be held elsewhere, such as by a signing agent.
+    pub fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = SignerCache(Arc::new(Mutex::new(Some(signer))));
+        self
+    }
+
+    /// Returns how far the Chef Server's clock is ahead of the local clock, as measured from
+    /// the most recent response. Clones of this client share the measurement.
+    pub fn clock_offset(&self) -> Option<chrono::Duration> {
+        self.clock.offset()
+    }
+
+    /// Sign requests with the local time adjusted by the measured clock offset, so that a host
+    /// with a skewed clock can still authenticate.
+    ///
+    /// The offset is only known once a response has been received, so when a request fails
+    /// with a `ClockSkewError` it is retried once with the corrected time.
+    pub fn with_clock_skew_compensation(mut self, compensate: bool) -> Self {
+        self.clock.compensate = compensate;
+        self
+    }
+
+    /// Set the default timeouts for requests made by this client. Each may also be overridden
+    /// on each request with `Execute::connect_timeout`, `Execute::read_timeout` and
+    /// `Execute::timeout`.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Returns a client for the organization `name` on the same Chef Server. It shares this
+    /// client's connection pool, credentials and settings.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// let nodes = api.for_organization("bedrock").unwrap().nodes().get();
+    /// ```
+    pub fn for_organization(&self, name: &str) -> Result<Self, Error> {
+        let mut api = self.clone();
+        api.config = self.config.for_organization(name)?;
+        Ok(api)
+    }
+
+    /// Lists every organization on the Chef Server with `ServerOrganizationsQuery`, returning
+    /// an iterator over a client for each, in order of name. Listing organizations requires a
+    /// server administrator's credentials.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// for org in api.organizations().unwrap() {
+    ///     let (name, api) = org.unwrap();
+    ///     let nodes = api.nodes().get().unwrap();
+    ///     println!("{}: {} nodes", name, nodes.as_object().map_or(0, |n| n.len()));
+    /// }
+    /// ```
+    pub fn organizations(&self) -> Result<Organizations, Error> {
+        let list = self.server_organizations().get()?;
+        let mut names = decode_list(&list)?;
+        names.sort();
+        Ok(Organizations {
+            api: self.clone(),
+            names: names.into_iter(),
+        })
+    }
+
+    /// Validates the configuration as `Config::validate` does, and then checks that the Chef
+    /// Server accepts the client's signature by fetching `/organizations/ORG/clients/NAME`.
+    pub fn validate(&self) -> Diagnosis {
+        let mut diagnosis = self.config.validate();
+        if diagnosis.is_ok() {
+            self.check_authentication(&mut diagnosis);
+        } else {
+            diagnosis.skip(AUTHENTICATION_CHECK);
+        }
+        diagnosis
+    }
+
+    /// Checks the credentials profile `profile`, chosen as for `from_credentials` but without
+    /// environment variable overrides, and reports the outcome of each check. See
+    /// `CredentialsFile::validate`. If `authenticate` is true and every other check passes,
+    /// the Chef Server is also asked to authenticate a request, as for `validate`.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// let diagnosis = ApiClient::doctor(None, true);
+    /// print!("{}", diagnosis);
+    /// ```
+    pub fn doctor(profile: Option<&str>, authenticate: bool) -> Diagnosis {
+        let profile = profile
+            .map(String::from)
+            .unwrap_or_else(credentials::active_profile);
+        let file = match CredentialsFile::open() {
+            Ok(file) => file,
+            Err(e) => {
+                let mut diagnosis = Diagnosis::default();
+                diagnosis.fail("credentials file is readable", &e.to_string());
+                return diagnosis;
+            }
+        };
+        let mut diagnosis = file.validate(&profile);
+        if !authenticate {
+            return diagnosis;
+        }
+        if !diagnosis.is_ok() {
+            diagnosis.skip(AUTHENTICATION_CHECK);
+            return diagnosis;
+        }
+        match file.profile(&profile).and_then(ApiClient::new) {
+            Ok(api) => api.check_authentication(&mut diagnosis),
+            Err(e) => diagnosis.fail(AUTHENTICATION_CHECK, &e.to_string()),
+        }
+        diagnosis
+    }
+
+    fn check_authentication(&self, diagnosis: &mut Diagnosis) {
+        let name = match self.config.client_name() {
+            Ok(name) => name,
+            Err(e) => return diagnosis.fail(AUTHENTICATION_CHECK, &e.to_string()),
+        };
+        match self.clients().client(name).get() {
+            Ok(_) => diagnosis.pass(
+                AUTHENTICATION_CHECK,
+                &format!("authenticated as client {}", name),
+            ),
+            Err(e) => match e.downcast_ref::<ChefError>() {
+                // Users authenticate too, but are not clients of the organization.
+                Some(ChefError::NotFoundError(_)) | Some(ChefError::ForbiddenError(_)) => diagnosis
+                    .pass(
+                        AUTHENTICATION_CHECK,
+                        &format!("authenticated as {}, which is not a client", name),
+                    ),
+                _ => diagnosis.fail(AUTHENTICATION_CHECK, &e.to_string()),
+            },
+        }
+    }
+
     build!(clients, ClientsQuery);
     build!(containers, ContainersQuery);
     build!(controls, ControlsQuery);
@@ -71,9 +449,493 @@ impl ApiClient {
     build!(server_organizations, ServerOrganizationsQuery);
     build!(server_users, ServerUsersQuery);
     build!(server_requests, ServerRequestsQuery);
+
+    /// Fetch many objects of the same type, making up to `concurrency` requests at once.
+    ///
+    /// Returns the result for each name, in the order given, so that a failure to fetch one
+    /// object does not prevent the others from being returned.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// let names = vec!["web1", "web2", "db1"];
+    /// for (name, node) in api.fetch_many(&BulkEndpoint::Nodes, &names, 4).unwrap() {
+    ///     match node {
+    ///         Ok(node) => println!("{}: {}", name, node["chef_environment"]),
+    ///         Err(e) => println!("{}: {}", name, e),
+    ///     }
+    /// }
+    /// ```
+    pub fn fetch_many<S>(
+        &self,
+        endpoint: &BulkEndpoint,
+        names: &[S],
+        concurrency: usize,
+    ) -> Result<BulkResults, Error>
+    where
+        S: AsRef<str>,
+    {
+        let requests = self.bulk_requests(endpoint, names);
+        let blocking = self.blocking_client()?;
+        blocking.block_on(fetch_all(blocking.client.clone(), requests, concurrency))
+    }
+
+    /// Fetch many objects of the same type asynchronously, making up to `concurrency` requests
+    /// at once. See `fetch_many`.
+    pub fn fetch_many_async<S>(
+        &self,
+        endpoint: &BulkEndpoint,
+        names: &[S],
+        concurrency: usize,
+    ) -> impl Future<Output = BulkResults> + Send + 'static
+    where
+        S: AsRef<str>,
+    {
+        let requests = self.bulk_requests(endpoint, names);
+        fetch_all(self.client.clone(), requests, concurrency)
+    }
+
+    fn bulk_requests<S>(&self, endpoint: &BulkEndpoint, names: &[S]) -> Vec<(String, ChefRequest)>
+    where
+        S: AsRef<str>,
+    {
+        names
+            .iter()
+            .map(|name| {
+                let name = name.as_ref();
+                (name.to_owned(), endpoint.request(self, name))
+            })
+            .collect()
+    }
+
+    pub(crate) fn execute_async(&self, request: ChefRequest) -> ResponseFuture {
+        Box::pin(request.send(self.client.clone()))
+    }
+
+    pub(crate) fn execute_blocking(&self, request: ChefRequest) -> Result<Value, Error> {
+        let blocking = self.blocking_client()?;
+        blocking.block_on(request.send(blocking.client.clone()))?
+    }
+
+    fn blocking_client(&self) -> Result<Arc<BlockingClient>, Error> {
+        // Waiting would stall every task on a single-threaded runtime. Threads of a
+        // multi-threaded runtime, including those running `spawn_blocking` closures, may wait.
+        if let Ok(handle) = Handle::try_current() {
+            if handle.runtime_flavor() == RuntimeFlavor::CurrentThread {
+                return Err(ChefError::BlockingClientError(String::from(
+                    "blocking requests can not be made from within a single-threaded async \
+                     runtime; use ExecuteAsync",
+                ))
+                .into());
+            }
+        }
+
+        let mut blocking = self.blocking.lock().map_err(|_| {
+            ChefError::BlockingClientError(String::from("a previous request panicked"))
+        })?;
+        if let Some(ref client) = *blocking {
+            return Ok(Arc::clone(client));
+        }
+
+        let client = Arc::new(BlockingClient::new(http_client(&self.config)?)?);
+        *blocking = Some(Arc::clone(&client));
+        Ok(client)
+    }
+}
+
+async fn fetch_all(
+    client: reqwest::Client,
+    requests: Vec<(String, ChefRequest)>,
+    concurrency: usize,
+) -> BulkResults {
+    stream::iter(requests)
+        .map(|(name, request)| {
+            let client = client.clone();
+            async move { (name, request.send(client).await) }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+fn http_client(config: &Config) -> Result<reqwest::Client, Error> {
+    let mut builder = reqwest::Client::builder()
+        .no_proxy()
+        .connector_layer(ConnectTimer);
+    if !config.verify_ssl()? {
+        warn!("Chef Server certificates will not be verified");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    for pem in config.trusted_certs()? {
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| ChefError::CertificateError(e.to_string()))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    for proxy in proxies(config)? {
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|e| ChefError::HTTPError(e).into())
+}
+
+/// Build the proxies described by the config, which draws on both the profile and the
+/// environment.
+fn proxies(config: &Config) -> Result<Vec<Proxy>, Error> {
+    let exclusions = config.proxy_exclusions();
+    let mut proxies = vec![];
+    for scheme in &["http", "https"] {
+        let url = match config.proxy_url(scheme) {
+            Some(url) => url,
+            None => continue,
+        };
+        debug!("Using proxy {} for {} requests", url, scheme);
+        let mut proxy = match *scheme {
+            "http" => Proxy::http(&url),
+            _ => Proxy::https(&url),
+        }
+        .map_err(ChefError::HTTPError)?;
+        if let Some((user, pass)) = config.proxy_credentials(scheme) {
+            proxy = proxy.basic_auth(&user, &pass);
+        }
+        proxies.push(proxy.no_proxy(exclusions.as_deref().and_then(NoProxy::from_string)));
+    }
+    Ok(proxies)
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+tokio::task_local! {
+    /// The connection state of the request being made by the current task.
+    static CONNECTION: Connection;
+}
+
+/// Shared between a request and the connector, so that each request's connect timeout applies
+/// to any new connection it opens, and its read timeout starts once that connection is made.
+#[derive(Debug, Clone)]
+struct Connection {
+    timeout: Option<Duration>,
+    phase: Arc<watch::Sender<Phase>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    /// No new connection has been needed yet, or a pooled connection was reused.
+    Idle,
+    Connecting,
+    Connected(Instant),
+}
+
+/// Wraps the HTTP client's connector to apply the connect timeout of the current request.
+#[derive(Debug, Clone, Copy)]
+struct ConnectTimer;
+
+impl<S> Layer<S> for ConnectTimer {
+    type Service = ConnectTimerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTimerService(inner)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConnectTimerService<S>(S);
+
+impl<S, R> Service<R> for ConnectTimerService<S>
+where
+    S: Service<R>,
+    S::Response: Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.0.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let connection = CONNECTION.try_with(Connection::clone).ok();
+        let connect = self.0.call(req);
+        Box::pin(async move {
+            let connection = match connection {
+                Some(connection) => connection,
+                None => return connect.await.map_err(Into::into),
+            };
+            connection.phase.send_replace(Phase::Connecting);
+            let result = match connection.timeout {
+                Some(limit) => match tokio::time::timeout(limit, connect).await {
+                    Ok(result) => result.map_err(Into::into),
+                    Err(_) => {
+                        Err(io::Error::new(io::ErrorKind::TimedOut, "connect timed out").into())
+                    }
+                },
+                None => connect.await.map_err(Into::into),
+            };
+            connection
+                .phase
+                .send_replace(Phase::Connected(Instant::now()));
+            result
+        })
+    }
+}
+
+/// A request captured from one of the request builders, holding everything needed to sign and
+/// send it.
+#[derive(Debug, Clone)]
+pub(crate) struct ChefRequest {
+    pub(crate) config: Config,
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) query: Vec<(String, String)>,
+    pub(crate) api_version: String,
+    pub(crate) body: String,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) timeouts: Timeouts,
+    pub(crate) signer: SignerCache,
+    pub(crate) clock: Clock,
+}
+
+impl ChefRequest {
+    async fn send(self, client: reqwest::Client) -> Result<Value, Error> {
+        let total = self.timeouts.total;
+        within(total, TimeoutKind::Total, self.send_with_retries(&client)).await?
+    }
+
+    async fn send_with_retries(&self, client: &reqwest::Client) -> Result<Value, Error> {
+        let mut attempt = 1;
+        let mut resigned = false;
+        loop {
+            let err = match self.attempt(client).await {
+                Ok(res) => return Ok(res),
+                Err(e) => e,
+            };
+            if self.clock.compensate && !resigned && is_clock_skew(&err) {
+                info!(
+                    "Retrying {} {} with the clock offset by {:?}",
+                    self.method,
+                    self.path,
+                    self.clock.offset()
+                );
+                resigned = true;
+                continue;
+            }
+            match self.retry_policy.retry_delay(attempt, &self.method, &err) {
+                Some(delay) => {
+                    info!(
+                        "Retrying {} {} in {:?} after attempt {} failed: {}",
+                        self.method, self.path, delay, attempt, err
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Err(err),
+            }
+        }
+    }
+
+    /// Sign and send the request once. Each attempt is signed with the current time.
+    async fn attempt(&self, client: &reqwest::Client) -> Result<Value, Error> {
+        // Loading the key may prompt for its passphrase, and a signer may wait on an agent, so
+        // both run where blocking is allowed rather than on the runtime's own threads.
+        let request = self.clone();
+        let headers = tokio::task::spawn_blocking(move || request.signed_headers())
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+
+        let url = format!(
+            "{}{}{}",
+            &self.config.url_base()?,
+            self.path,
+            encode_query(&self.query)
+        );
+
+        let method = match self.method.as_str() {
+            "put" => Method::PUT,
+            "post" => Method::POST,
+            "delete" => Method::DELETE,
+            "head" => Method::HEAD,
+            _ => Method::GET,
+        };
+
+        let read = self.timeouts.read;
+        let (phase, progress) = watch::channel(Phase::Idle);
+        let connection = Connection {
+            timeout: self.timeouts.connect,
+            phase: Arc::new(phase),
+        };
+        let request = client
+            .request(method, &url)
+            .headers(headers)
+            .body(self.body.clone())
+            .send();
+        let mut res = read_within(read, progress, CONNECTION.scope(connection, request))
+            .await?
+            .map_err(
+                |e| match (self.timeouts.connect, e.is_connect() && e.is_timeout()) {
+                    (Some(connect), true) => ChefError::TimeoutError(TimeoutKind::Connect, connect),
+                    _ => ChefError::HTTPError(e),
+                },
+            )?;
+
+        debug!("Status is {:?}", res.status());
+        let status = res.status();
+        let headers = res.headers().clone();
+        self.clock.record(&headers);
+        let mut body = vec![];
+        while let Some(chunk) = within(read, TimeoutKind::Read, res.chunk())
+            .await?
+            .map_err(ChefError::HTTPError)?
+        {
+            body.extend_from_slice(&chunk);
+        }
+
+        if status.is_success() {
+            parse_body(&body)
+        } else {
+            let path = format!("{}{}", self.path, encode_query(&self.query));
+            let err = ServerError::new(status.as_u16(), &self.method, &path, headers, &body);
+            Err(ChefError::from(err).into())
+        }
+    }
+
+    /// Sign the request with the current time, returning its headers. This may block.
+    fn signed_headers(&self) -> Result<HeaderMap, Error> {
+        let userid = self.config.client_name()?;
+        let signer = self.signer.get(&self.config)?;
+
+        let mut headers = HeaderMap::new();
+        self.config.signing_protocol()?.sign(
+            &self.path,
+            signer.as_ref(),
+            &self.method,
+            userid,
+            &self.api_version,
+            Some(self.body.clone()),
+            self.clock.now(),
+            &mut headers,
+        )?;
+
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(OPS_API_INFO, HeaderValue::from_static("1"));
+        headers.insert(OPS_API_VERSION, HeaderValue::from_str(&self.api_version)?);
+        headers.insert(CHEF_VERSION, HeaderValue::from_static("13.3.34"));
+        Ok(headers)
+    }
+}
+
+fn is_clock_skew(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref::<ChefError>(),
+        Some(ChefError::ClockSkewError(_))
+    )
 }
 
-/// The Execute trait finalises and executes a request, by making the desired HTTP call.
+/// Parse a successful response. Responses without a body, such as those to `head` requests or
+/// with a `204 No Content` status, are returned as `Value::Null`.
+fn parse_body(body: &[u8]) -> Result<Value, Error> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Value::Null);
+    }
+    serde_json::from_slice(body).map_err(|e| ChefError::JsonError(e).into())
+}
+
+/// Await the response to a request, failing with a read `TimeoutError` if it does not start
+/// within `limit` of a new connection being made, or of the request starting when no new
+/// connection is needed. Time spent connecting is limited by the connect timeout instead.
+async fn read_within<F: Future>(
+    limit: Option<Duration>,
+    mut phase: watch::Receiver<Phase>,
+    fut: F,
+) -> Result<F::Output, ChefError> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(fut.await),
+    };
+    let start = Instant::now();
+    let mut fut = std::pin::pin!(fut);
+    loop {
+        let deadline = match *phase.borrow_and_update() {
+            Phase::Idle => Some(start + limit),
+            Phase::Connecting => None,
+            Phase::Connected(at) => Some(at + limit),
+        };
+        let timer = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => future::pending().await,
+            }
+        };
+        tokio::select! {
+            output = &mut fut => return Ok(output),
+            Ok(()) = phase.changed() => continue,
+            _ = timer => {
+                if !phase.has_changed().unwrap_or(false) {
+                    return Err(ChefError::TimeoutError(TimeoutKind::Read, limit));
+                }
+            }
+        }
+    }
+}
+
+/// Await `fut`, failing with a `TimeoutError` if it does not complete within `limit`.
+async fn within<F: Future>(
+    limit: Option<Duration>,
+    kind: TimeoutKind,
+    fut: F,
+) -> Result<F::Output, ChefError> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, fut)
+            .await
+            .map_err(|_| ChefError::TimeoutError(kind, limit)),
+        None => Ok(fut.await),
+    }
+}
+
+pub(crate) fn serialize_body<B>(body: Option<B>) -> Result<String, Error>
+where
+    B: Serialize,
+{
+    match body {
+        Some(b) => serde_json::to_string(&b).map_err(|e| ChefError::JsonError(e).into()),
+        None => Ok(String::from(EMPTY_BODY)),
+    }
+}
+
+pub(crate) fn deserialize_response<T>(response: Value) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_value(response).map_err(|e| ChefError::JsonError(e).into())
+}
+
+fn existence(response: Result<Value, Error>) -> Result<bool, Error> {
+    match response {
+        Ok(_) => Ok(true),
+        Err(e) => match e.downcast_ref::<ChefError>() {
+            Some(ChefError::NotFoundError(_)) => Ok(false),
+            _ => Err(e),
+        },
+    }
+}
+
+fn deserialize_future<T>(response: ResponseFuture) -> TypedResponseFuture<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    Box::pin(async move { deserialize_response(response.await?) })
+}
+
+/// The Execute trait finalises and executes a request, by making the desired HTTP call and
+/// blocking until it completes.
+///
+/// These methods may be called from any thread where blocking is allowed, including the worker
+/// threads of a multi-threaded tokio runtime and closures run with `spawn_blocking`. On a
+/// single-threaded runtime they return a `BlockingClientError`; use `ExecuteAsync` there
+/// instead.
 pub trait Execute {
     /// Make an HTTP `get` request.
     fn get(&self) -> Result<Value, Error> {
@@ -85,11 +947,27 @@ pub trait Execute {
         self.execute(None::<String>, "delete")
     }
 
-    /// Make an HTTP `head` request.
+    /// Make an HTTP `head` request. A successful response has no body, so returns `Value::Null`.
     fn head(&self) -> Result<Value, Error> {
         self.execute(None::<String>, "head")
     }
 
+    /// Check whether the object at this path exists, using a `head` request.
+    ///
+    /// Returns `false` if the Chef Server responds with `404 Not Found`; any other failure is
+    /// returned as an error.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// if !api.nodes().node("my_node").exists().unwrap() {
+    ///     println!("my_node has not been registered");
+    /// }
+    /// ```
+    fn exists(&self) -> Result<bool, Error> {
+        existence(self.head())
+    }
+
     /// Make an HTTP `post` request, accepting a `T` which can be converted into JSON.
     fn post<B>(&self, body: &B) -> Result<Value, Error>
     where
@@ -106,11 +984,560 @@ pub trait Execute {
         self.execute(Some(body), "put")
     }
 
+    /// Make an HTTP `get` request, deserializing the response into a `T`.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # use std::collections::HashMap;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// let nodes: HashMap<String, String> = api.nodes().get_as().unwrap();
+    /// ```
+    fn get_as<T>(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        deserialize_response(self.get()?)
+    }
+
+    /// Make an HTTP `delete` request, deserializing the response into a `T`.
+    fn delete_as<T>(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        deserialize_response(self.delete()?)
+    }
+
+    /// Make an HTTP `post` request, deserializing the response into a `T`.
+    fn post_as<T, B>(&self, body: &B) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        B: Serialize,
+    {
+        deserialize_response(self.post(body)?)
+    }
+
+    /// Make an HTTP `put` request, deserializing the response into a `T`.
+    fn put_as<T, B>(&self, body: &B) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        B: Serialize,
+    {
+        deserialize_response(self.put(body)?)
+    }
+
     /// Set the Chef API Version.
     fn api_version(&mut self, api_version: &str) -> &mut Self;
 
+    /// Set a query string parameter on the request, replacing any previous value for `key`.
+    /// Parameters are URL encoded when the request is made, and are not included in the path
+    /// used to sign the request.
+    fn query_param(&mut self, key: &str, value: &str) -> &mut Self;
+
+    /// Limit how long this request may take, including any retries, overriding the client's
+    /// total timeout.
+    fn timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Limit how long to wait for the response, and each part of its body, overriding the
+    /// client's read timeout.
+    fn read_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Limit how long to wait when opening a new connection for this request, overriding the
+    /// client's connect timeout.
+    fn connect_timeout(&mut self, timeout: Duration) -> &mut Self;
+
     #[doc(hidden)]
     fn execute<B>(&self, body: Option<B>, method: &str) -> Result<Value, Error>
     where
         B: Serialize;
 }
+
+/// The ExecuteAsync trait finalises a request, returning a future that makes the desired HTTP
+/// call when polled.
+///
+/// The returned futures own everything they need, so they are `Send` and `'static` and may be
+/// spawned on to any tokio runtime.
+///
+/// ```rust,no_run
+/// # use chef_api::api_client::*;
+/// # async fn run() -> Result<(), failure::Error> {
+/// let api = ApiClient::from_credentials(None)?;
+/// let node = api.nodes().node("my_node").get_async();
+/// let role = api.roles().role("web").get_async();
+/// let (node, role) = (tokio::spawn(node), tokio::spawn(role));
+/// println!("{:?} {:?}", node.await?, role.await?);
+/// # Ok(())
+/// # }
+/// ```
+pub trait ExecuteAsync {
+    /// Make an HTTP `get` request.
+    fn get_async(&self) -> ResponseFuture {
+        self.execute_async(None::<String>, "get")
+    }
+
+    /// Make an HTTP `delete` request.
+    fn delete_async(&self) -> ResponseFuture {
+        self.execute_async(None::<String>, "delete")
+    }
+
+    /// Make an HTTP `head` request. A successful response has no body, so resolves to
+    /// `Value::Null`.
+    fn head_async(&self) -> ResponseFuture {
+        self.execute_async(None::<String>, "head")
+    }
+
+    /// Check whether the object at this path exists, using a `head` request.
+    fn exists_async(&self) -> ExistsFuture {
+        let response = self.head_async();
+        Box::pin(async move { existence(response.await) })
+    }
+
+    /// Make an HTTP `post` request, accepting a `T` which can be converted into JSON.
+    fn post_async<B>(&self, body: &B) -> ResponseFuture
+    where
+        B: Serialize,
+    {
+        self.execute_async(Some(body), "post")
+    }
+
+    /// Make an HTTP `put` request, accepting a `T` which can be converted into JSON.
+    fn put_async<B>(&self, body: &B) -> ResponseFuture
+    where
+        B: Serialize,
+    {
+        self.execute_async(Some(body), "put")
+    }
+
+    /// Make an HTTP `get` request, deserializing the response into a `T`.
+    fn get_as_async<T>(&self) -> TypedResponseFuture<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        deserialize_future(self.get_async())
+    }
+
+    /// Make an HTTP `delete` request, deserializing the response into a `T`.
+    fn delete_as_async<T>(&self) -> TypedResponseFuture<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        deserialize_future(self.delete_async())
+    }
+
+    /// Make an HTTP `post` request, deserializing the response into a `T`.
+    fn post_as_async<T, B>(&self, body: &B) -> TypedResponseFuture<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+        B: Serialize,
+    {
+        deserialize_future(self.post_async(body))
+    }
+
+    /// Make an HTTP `put` request, deserializing the response into a `T`.
+    fn put_as_async<T, B>(&self, body: &B) -> TypedResponseFuture<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+        B: Serialize,
+    {
+        deserialize_future(self.put_async(body))
+    }
+
+    #[doc(hidden)]
+    fn execute_async<B>(&self, body: Option<B>, method: &str) -> ResponseFuture
+    where
+        B: Serialize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_api_client_is_send_and_sync() {
+        assert_send_sync::<ApiClient>();
+    }
+
+    #[test]
+    fn test_bulk_endpoint_paths() {
+        let api = api_client("https://localhost/organizations/bedrock");
+        let bag = BulkEndpoint::DataBagItems(String::from("users"));
+        assert_eq!(
+            bag.request(&api, "fred").path,
+            "/organizations/bedrock/data/users/fred"
+        );
+        assert_eq!(
+            BulkEndpoint::Nodes.request(&api, "web1").path,
+            "/organizations/bedrock/nodes/web1"
+        )
+    }
+
+    #[test]
+    fn test_fetch_many_returns_each_result() {
+        // Nothing listens on the discard port, so every request fails to connect.
+        let api = api_client("http://127.0.0.1:9/organizations/bedrock");
+        let results = api
+            .fetch_many(&BulkEndpoint::Roles, &["web", "db", "cache"], 2)
+            .unwrap();
+        let names: Vec<&str> = results.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["web", "db", "cache"]);
+        assert!(results.iter().all(|(_, r)| r.is_err()))
+    }
+
+    #[test]
+    fn test_fetch_many_mixed_results() {
+        // With one request at a time, the responses are served in the order of the names.
+        let (url, requests) = serve(vec![
+            json_response(r#"{"name": "web"}"#),
+            String::from(
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            ),
+        ]);
+        let results = api_client(&url)
+            .fetch_many(&BulkEndpoint::Roles, &["web", "db"], 1)
+            .unwrap();
+
+        let paths: Vec<String> = requests
+            .iter()
+            .take(2)
+            .map(|r| r.lines().next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "GET /organizations/bedrock/roles/web HTTP/1.1",
+                "GET /organizations/bedrock/roles/db HTTP/1.1"
+            ]
+        );
+        assert_eq!(results[0].0, "web");
+        assert_eq!(results[0].1.as_ref().unwrap()["name"], "web");
+        assert_eq!(results[1].0, "db");
+        match results[1]
+            .1
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<ChefError>()
+        {
+            Some(ChefError::NotFoundError(err)) => assert_eq!(err.status, 404),
+            _ => panic!("expected a not found error for db"),
+        }
+    }
+
+    /// A connector that never manages to connect.
+    #[derive(Clone)]
+    struct Stalled;
+
+    impl Service<()> for Stalled {
+        type Response = ();
+        type Error = io::Error;
+        type Future = future::Pending<Result<(), io::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            future::pending()
+        }
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        let rt = RuntimeBuilder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (phase, progress) = watch::channel(Phase::Idle);
+        let connection = Connection {
+            timeout: Some(Duration::from_millis(50)),
+            phase: Arc::new(phase),
+        };
+        let mut connector = ConnectTimer.layer(Stalled);
+        let err = rt
+            .block_on(CONNECTION.scope(connection, async { connector.call(()).await }))
+            .unwrap_err();
+        let err = err.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(matches!(*progress.borrow(), Phase::Connected(_)))
+    }
+
+    #[test]
+    fn test_read_timeout_starts_once_connected() {
+        let rt = RuntimeBuilder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let read = Some(Duration::from_millis(100));
+        let slow_connect = |response_after: u64| {
+            let (phase, progress) = watch::channel(Phase::Connecting);
+            let response = async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                phase.send_replace(Phase::Connected(Instant::now()));
+                tokio::time::sleep(Duration::from_millis(response_after)).await;
+            };
+            read_within(read, progress, response)
+        };
+
+        assert!(rt.block_on(slow_connect(20)).is_ok());
+        match rt.block_on(slow_connect(400)) {
+            Err(ChefError::TimeoutError(kind, _)) => assert_eq!(kind, TimeoutKind::Read),
+            _ => panic!("expected a read timeout"),
+        }
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        // The listener accepts connections into its backlog, but never responds.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/organizations/bedrock",
+            listener.local_addr().unwrap()
+        );
+        let api = api_client(&url);
+        let err = api
+            .nodes()
+            .read_timeout(Duration::from_millis(50))
+            .get()
+            .unwrap_err();
+        match err.downcast_ref::<ChefError>() {
+            Some(ChefError::TimeoutError(kind, _)) => assert_eq!(*kind, TimeoutKind::Read),
+            _ => panic!("expected a read timeout, got {}", err),
+        }
+    }
+
+    #[test]
+    fn test_parse_empty_body() {
+        assert_eq!(parse_body(b"").unwrap(), Value::Null);
+        assert_eq!(parse_body(b" \n").unwrap(), Value::Null);
+        assert_eq!(parse_body(b"{\"name\":\"web1\"}").unwrap()["name"], "web1");
+        assert!(parse_body(b"<html>").is_err())
+    }
+
+    #[test]
+    fn test_no_content_response() {
+        let api = api_client(&respond_once("204 No Content"));
+        assert_eq!(api.nodes().node("web1").delete().unwrap(), Value::Null)
+    }
+
+    #[test]
+    fn test_exists() {
+        let api = api_client(&respond_once("200 OK"));
+        assert!(api.nodes().node("web1").exists().unwrap());
+        let api = api_client(&respond_once("404 Not Found"));
+        assert!(!api.nodes().node("web1").exists().unwrap());
+        let api = api_client(&respond_once("403 Forbidden"));
+        assert!(api.nodes().node("web1").exists().is_err())
+    }
+
+    #[test]
+    fn test_organizations() {
+        let body = r#"{"slate":"https://localhost/organizations/slate","bedrock":"https://localhost/organizations/bedrock"}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (url, requests) = serve(vec![response]);
+        let api = api_client(&url);
+        let orgs: Vec<_> = api
+            .organizations()
+            .unwrap()
+            .map(|org| org.unwrap())
+            .collect();
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("GET /organizations "), "{}", request);
+
+        let names: Vec<_> = orgs.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["bedrock", "slate"]);
+        let (_, slate) = &orgs[1];
+        assert_eq!(
+            slate.config.organization_path().unwrap(),
+            "/organizations/slate"
+        );
+        assert_eq!(
+            slate.nodes().node("web1").path,
+            "/organizations/slate/nodes/web1"
+        )
+    }
+
+    #[test]
+    fn test_invalid_server_url() {
+        let config = Config::from_str(
+            "[default]\nclient_name = 'spec-user'\nclient_key = 'spec-user.pem'\n\
+             chef_server_url = 'chef.local/organizations/bedrock'",
+            "default",
+        )
+        .unwrap();
+        assert!(ApiClient::new(config).is_err());
+
+        // Changing the URL after construction fails when the request is executed.
+        let mut api = api_client("https://localhost/organizations/bedrock");
+        api.config.chef_server_url = String::from("not a url");
+        let err = api.nodes().node("web1").get().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChefError>(),
+            Some(ChefError::UrlParseError(_))
+        ))
+    }
+
+    #[test]
+    fn test_validate() {
+        let diagnosis = api_client(&respond_once("200 OK")).validate();
+        assert!(diagnosis.is_ok(), "{}", diagnosis);
+        assert!(diagnosis.passed(AUTHENTICATION_CHECK));
+
+        let diagnosis = api_client(&respond_once("401 Unauthorized")).validate();
+        let failures: Vec<_> = diagnosis.failures().map(|c| c.name.as_str()).collect();
+        assert_eq!(failures, vec![AUTHENTICATION_CHECK]);
+
+        let diagnosis = api_client("https://localhost/bedrock").validate();
+        let failures: Vec<_> = diagnosis.failures().map(|c| c.name.as_str()).collect();
+        assert_eq!(failures, vec!["chef_server_url includes an organization"]);
+        assert_eq!(
+            diagnosis.checks.last().unwrap().status,
+            credentials::CheckStatus::Skipped
+        )
+    }
+
+    #[test]
+    fn test_clock_skew_compensation() {
+        let server_time = chrono::Utc::now() + chrono::Duration::minutes(30);
+        let date = server_time.to_rfc2822();
+        let skewed = format!(
+            "HTTP/1.1 401 Unauthorized\r\nDate: {}\r\nContent-Length: 0\r\n\
+             Connection: close\r\n\r\n",
+            date
+        );
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nDate: {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+            date
+        );
+
+        let (url, _) = serve(vec![skewed.clone()]);
+        let err = api_client(&url).nodes().get().unwrap_err();
+        assert!(is_clock_skew(&err));
+
+        let (url, requests) = serve(vec![skewed, ok]);
+        let api = api_client(&url).with_clock_skew_compensation(true);
+        assert!(api.nodes().get().is_ok());
+        let offset = api.clock_offset().unwrap();
+        assert!(offset > chrono::Duration::minutes(29));
+
+        let retried = requests.iter().nth(1).unwrap();
+        let stamp = retried
+            .lines()
+            .find_map(|l| {
+                l.to_ascii_lowercase()
+                    .strip_prefix("x-ops-timestamp: ")
+                    .map(String::from)
+            })
+            .unwrap();
+        let stamp = chrono::DateTime::parse_from_rfc3339(&stamp.to_ascii_uppercase()).unwrap();
+        assert!(
+            (stamp.with_timezone(&chrono::Utc) - server_time)
+                .num_seconds()
+                .abs()
+                < 60
+        )
+    }
+
+    #[test]
+    fn test_key_is_loaded_once() {
+        let pem = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/spec-user.pem"
+        ))
+        .unwrap();
+        let encrypted = openssl::rsa::Rsa::private_key_from_pem(&pem)
+            .unwrap()
+            .private_key_to_pem_passphrase(openssl::symm::Cipher::aes_256_cbc(), b"secret")
+            .unwrap();
+        let prompts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&prompts);
+
+        let unavailable = String::from(
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+        let (url, _) = serve(vec![unavailable, json_response("{}"), json_response("{}")]);
+        let config = Config::from_key(&url, "spec-user", &encrypted).with_passphrase(move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(String::from("secret"))
+        });
+        let policy = RetryPolicy::new(2).initial_backoff(Duration::from_millis(10));
+        let api = ApiClient::new(config).unwrap().with_retry_policy(policy);
+
+        assert!(api.nodes().get().is_ok());
+        assert!(api.clone().roles().get().is_ok());
+        assert_eq!(prompts.load(std::sync::atomic::Ordering::SeqCst), 1)
+    }
+
+    /// A signer that takes a while to respond, like a remote signing agent.
+    #[derive(Debug)]
+    struct SlowSigner(crate::authentication::signer::KeySigner);
+
+    impl Signer for SlowSigner {
+        fn private_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+            std::thread::sleep(Duration::from_millis(300));
+            self.0.private_encrypt(data)
+        }
+
+        fn sign(
+            &self,
+            algorithm: crate::authentication::SigningAlgorithm,
+            data: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            std::thread::sleep(Duration::from_millis(300));
+            self.0.sign(algorithm, data)
+        }
+    }
+
+    #[test]
+    fn test_slow_signer_does_not_block_runtime() {
+        let (url, _) = serve(vec![json_response("{}")]);
+        let api = api_client(&url);
+        let signer = Arc::new(SlowSigner(api.config.signer().unwrap()));
+        let api = api.with_signer(signer);
+
+        let rt = RuntimeBuilder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        let (res, ticked) = rt.block_on(async {
+            let tick = async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                start.elapsed()
+            };
+            tokio::join!(api.nodes().get_async(), tick)
+        });
+        assert!(res.is_ok());
+        assert!(ticked < Duration::from_millis(250), "{:?}", ticked)
+    }
+
+    #[test]
+    fn test_blocking_request_inside_runtime() {
+        let api = api_client("https://localhost/organizations/bedrock");
+        let rt = RuntimeBuilder::new_current_thread().build().unwrap();
+        let res = rt.block_on(async { api.nodes().get() });
+        assert!(res.is_err())
+    }
+
+    #[test]
+    fn test_drop_blocking_client_inside_runtime() {
+        let api = api_client(&respond_once("200 OK"));
+        assert!(api.nodes().get().is_ok());
+        let rt = RuntimeBuilder::new_current_thread().build().unwrap();
+        rt.block_on(async move { drop(api) });
+    }
+
+    #[test]
+    fn test_blocking_request_from_spawn_blocking() {
+        let api = api_client(&respond_once("200 OK"));
+        let rt = RuntimeBuilder::new_multi_thread().build().unwrap();
+        let res = rt
+            .block_on(async { tokio::task::spawn_blocking(move || api.nodes().get()).await })
+            .unwrap();
+        assert!(res.is_ok())
+    }
+}
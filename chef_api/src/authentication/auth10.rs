@@ -0,0 +1,166 @@
+use crate::authentication::signer::Signer;
+use crate::authentication::BASE64_AUTH;
+use crate::http_headers::*;
+use crate::utils::{expand_string, squeeze_path, strip_query};
+use chrono::*;
+use failure::Error;
+use openssl::hash::{hash, MessageDigest};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rustc_serialize::base64::ToBase64;
+use std::fmt;
+
+pub struct Auth10<'s> {
+    #[allow(dead_code)]
+    api_version: String,
+    body: Option<String>,
+    date: String,
+    signer: &'s dyn Signer,
+    method: String,
+    path: String,
+    userid: String,
+}
+
+impl<'s> fmt::Debug for Auth10<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Auth10")
+            .field("method", &self.method)
+            .field("userid", &self.userid)
+            .field("path", &self.path)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl<'s> Auth10<'s> {
+    pub fn new(
+        path: &str,
+        signer: &'s dyn Signer,
+        method: &str,
+        userid: &str,
+        api_version: &str,
+        body: Option<String>,
+    ) -> Auth10<'s> {
+        let dt = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+
+        let userid: String = userid.into();
+        let method = String::from(method).to_ascii_uppercase();
+
+        Auth10 {
+            api_version: api_version.into(),
+            body,
+            date: dt,
+            signer,
+            method,
+            path: squeeze_path(strip_query(path)),
+            userid,
+        }
+    }
+
+    fn hashed_path(&self) -> Result<String, Error> {
+        debug!("Path is: {:?}", self.path);
+        let hash = hash(MessageDigest::sha1(), self.path.as_bytes())?.to_base64(BASE64_AUTH);
+        Ok(hash)
+    }
+
+    /// Sign the request as if made at `timestamp`, rather than the current time.
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Auth10<'s> {
+        self.date = timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        self
+    }
+
+    fn content_hash(&self) -> Result<String, Error> {
+        let body = expand_string(&self.body);
+        let content = hash(MessageDigest::sha1(), body.as_bytes())?.to_base64(BASE64_AUTH);
+        debug!("{:?}", content);
+        Ok(content)
+    }
+
+    fn canonical_request(&self) -> Result<String, Error> {
+        let cr = format!(
+            "Method:{}\nHashed Path:{}\n\
+             X-Ops-Content-Hash:{}\n\
+             X-Ops-Timestamp:{}\nX-Ops-UserId:{}",
+            &self.method,
+            self.hashed_path()?,
+            self.content_hash()?,
+            self.date,
+            &self.userid
+        );
+        debug!("Canonical Request is: {:?}", cr);
+        Ok(cr)
+    }
+
+    fn encrypted_request(&self) -> Result<String, Error> {
+        let cr = self.canonical_request()?;
+        let hash = self.signer.private_encrypt(cr.as_bytes())?;
+        Ok(hash.to_base64(BASE64_AUTH))
+    }
+
+    pub fn build(self, headers: &mut HeaderMap) -> Result<(), Error> {
+        let hsh = self.content_hash()?;
+        headers.insert(OPS_CONTENT_HASH, HeaderValue::from_str(&hsh)?);
+        headers.insert(
+            OPS_SIGN,
+            HeaderValue::from_static("algorithm=sha1;version=1.0"),
+        );
+        headers.insert(OPS_TIMESTAMP, HeaderValue::from_str(&self.date)?);
+        headers.insert(OPS_USERID, HeaderValue::from_str(&self.userid)?);
+
+        let enc = self.encrypted_request()?;
+        for (i, h) in enc.split('\n').enumerate() {
+            let key = format!("{}-{}", OPS_AUTHORIZATION, i + 1);
+            headers.insert(
+                HeaderName::from_bytes(key.as_bytes())?,
+                HeaderValue::from_str(h)?,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Auth10;
+    use crate::authentication::signer::KeySigner;
+    use std::fs::File;
+    use std::io::Read;
+
+    const PATH: &str = "/organizations/clownco";
+    const BODY: &str = "Spec Body";
+    const USER: &str = "spec-user";
+    const DT: &str = "2009-01-01T12:00:00Z";
+
+    const PRIVATE_KEY: &str = "fixtures/spec-user.pem";
+
+    fn get_key_data() -> Vec<u8> {
+        let mut key = String::new();
+        File::open(PRIVATE_KEY)
+            .and_then(|mut fh| fh.read_to_string(&mut key))
+            .unwrap();
+        key.into_bytes()
+    }
+
+    fn get_signer() -> KeySigner {
+        KeySigner::from_pem(&get_key_data()).unwrap()
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        let auth = Auth10 {
+            api_version: String::from("1"),
+            body: Some(String::from(BODY)),
+            date: String::from(DT),
+            signer: &get_signer(),
+            method: String::from("POST"),
+            path: String::from(PATH),
+            userid: String::from(USER),
+        };
+        assert_eq!(
+            auth.canonical_request().unwrap(),
+            "Method:POST\nHashed \
+             Path:YtBWDn1blGGuFIuKksdwXzHU9oE=\nX-Ops-Content-Hash:\
+             DFteJZPVv6WKdQmMqZUQUumUyRs=\nX-Ops-Timestamp:2009-01-01T12:00:\
+             00Z\nX-Ops-UserId:spec-user"
+        )
+    }
+}
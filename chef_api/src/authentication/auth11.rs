@@ -1,27 +1,26 @@
+use crate::authentication::signer::Signer;
 use crate::authentication::BASE64_AUTH;
 use crate::http_headers::*;
-use crate::utils::{expand_string, squeeze_path};
+use crate::utils::{expand_string, squeeze_path, strip_query};
 use chrono::*;
 use failure::Error;
-use hyper::header::Headers;
 use openssl::hash::{hash, MessageDigest};
-use openssl::rsa::Padding;
-use openssl::rsa::Rsa;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use rustc_serialize::base64::ToBase64;
 use std::fmt;
 
-pub struct Auth11 {
+pub struct Auth11<'s> {
     #[allow(dead_code)]
     api_version: String,
     body: Option<String>,
     date: String,
-    key: Vec<u8>,
+    signer: &'s dyn Signer,
     method: String,
     path: String,
     userid: String,
 }
 
-impl fmt::Debug for Auth11 {
+impl<'s> fmt::Debug for Auth11<'s> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Auth11")
             .field("method", &self.method)
@@ -32,15 +31,15 @@ impl fmt::Debug for Auth11 {
     }
 }
 
-impl Auth11 {
+impl<'s> Auth11<'s> {
     pub fn new(
         path: &str,
-        key: &[u8],
+        signer: &'s dyn Signer,
         method: &str,
         userid: &str,
         api_version: &str,
         body: Option<String>,
-    ) -> Auth11 {
+    ) -> Auth11<'s> {
         let dt = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
 
         let userid: String = userid.into();
@@ -50,9 +49,9 @@ impl Auth11 {
             api_version: api_version.into(),
             body,
             date: dt,
-            key: key.into(),
+            signer,
             method,
-            path: squeeze_path(path),
+            path: squeeze_path(strip_query(path)),
             userid,
         }
     }
@@ -63,6 +62,12 @@ impl Auth11 {
         Ok(hash)
     }
 
+    /// Sign the request as if made at `timestamp`, rather than the current time.
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Auth11<'s> {
+        self.date = timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        self
+    }
+
     fn content_hash(&self) -> Result<String, Error> {
         let body = expand_string(&self.body);
         let content = hash(MessageDigest::sha1(), body.as_bytes())?.to_base64(BASE64_AUTH);
@@ -72,7 +77,7 @@ impl Auth11 {
 
     fn canonical_user_id(&self) -> Result<String, Error> {
         hash(MessageDigest::sha1(), self.userid.as_bytes())
-            .and_then(|res| Ok(res.to_base64(BASE64_AUTH)))
+            .map(|res| res.to_base64(BASE64_AUTH))
             .map_err(|res| res.into())
     }
 
@@ -92,30 +97,28 @@ impl Auth11 {
     }
 
     fn encrypted_request(&self) -> Result<String, Error> {
-        let key = Rsa::private_key_from_pem(self.key.as_slice())?;
-
         let cr = self.canonical_request()?;
-        let cr = cr.as_bytes();
-
-        let mut hash: Vec<u8> = vec![0; key.size() as usize];
-        key.private_encrypt(cr, &mut hash, Padding::PKCS1)?;
+        let hash = self.signer.private_encrypt(cr.as_bytes())?;
         Ok(hash.to_base64(BASE64_AUTH))
     }
 
-    pub fn build(self, headers: &mut Headers) -> Result<(), Error> {
+    pub fn build(self, headers: &mut HeaderMap) -> Result<(), Error> {
         let hsh = self.content_hash()?;
-        headers.set(OpsContentHash(hsh));
-
-        headers.set(OpsSign(String::from("algorithm=sha1;version=1.1")));
-        headers.set(OpsTimestamp(self.date.clone()));
-        headers.set(OpsUserId(self.userid.clone()));
+        headers.insert(OPS_CONTENT_HASH, HeaderValue::from_str(&hsh)?);
+        headers.insert(
+            OPS_SIGN,
+            HeaderValue::from_static("algorithm=sha1;version=1.1"),
+        );
+        headers.insert(OPS_TIMESTAMP, HeaderValue::from_str(&self.date)?);
+        headers.insert(OPS_USERID, HeaderValue::from_str(&self.userid)?);
 
         let enc = self.encrypted_request()?;
-        let mut i = 1;
-        for h in enc.split('\n') {
-            let key = format!("X-Ops-Authorization-{}", i);
-            headers.set_raw(key, vec![h.as_bytes().to_vec()]);
-            i += 1;
+        for (i, h) in enc.split('\n').enumerate() {
+            let key = format!("{}-{}", OPS_AUTHORIZATION, i + 1);
+            headers.insert(
+                HeaderName::from_bytes(key.as_bytes())?,
+                HeaderValue::from_str(h)?,
+            );
         }
         Ok(())
     }
@@ -124,6 +127,7 @@ impl Auth11 {
 #[cfg(test)]
 mod tests {
     use super::Auth11;
+    use crate::authentication::signer::KeySigner;
     use std::fs::File;
     use std::io::Read;
 
@@ -142,13 +146,17 @@ mod tests {
         key.into_bytes()
     }
 
+    fn get_signer() -> KeySigner {
+        KeySigner::from_pem(&get_key_data()).unwrap()
+    }
+
     #[test]
     fn test_canonical_user_id() {
         let auth = Auth11 {
             api_version: String::from("1"),
             body: Some(String::from(BODY)),
             date: String::from(DT),
-            key: get_key_data(),
+            signer: &get_signer(),
             method: String::from("POST"),
             path: String::from(PATH),
             userid: String::from(USER),
@@ -165,7 +173,7 @@ mod tests {
             api_version: String::from("1"),
             body: Some(String::from(BODY)),
             date: String::from(DT),
-            key: get_key_data(),
+            signer: &get_signer(),
             method: String::from("POST"),
             path: String::from(PATH),
             userid: String::from(USER),
@@ -185,7 +193,7 @@ mod tests {
             api_version: String::from("1"),
             body: Some(String::from(BODY)),
             date: String::from(DT),
-            key: get_key_data(),
+            signer: &get_signer(),
             method: String::from("POST"),
             path: String::from(PATH),
             userid: String::from(USER),
@@ -200,5 +208,4 @@ mod tests {
              FDlbAG7H8Dmvo+wBxmtNkszhzbBnEYtuwQqT8nM/8A=="
         )
     }
-
 }
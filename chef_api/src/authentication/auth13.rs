@@ -1,26 +1,26 @@
-use crate::authentication::BASE64_AUTH;
+use crate::authentication::signer::Signer;
+use crate::authentication::{SigningAlgorithm, SigningProtocol, BASE64_AUTH};
 use crate::http_headers::*;
-use crate::utils::{expand_string, squeeze_path};
+use crate::utils::{expand_string, squeeze_path, strip_query};
 use chrono::*;
 use failure::Error;
-use hyper::header::Headers;
-use openssl::hash::{hash, MessageDigest};
-use openssl::pkey::PKey;
-use openssl::sign::Signer;
+use openssl::hash::hash;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use rustc_serialize::base64::ToBase64;
 use std::fmt;
 
-pub struct Auth13 {
+pub struct Auth13<'s> {
+    algorithm: SigningAlgorithm,
     api_version: String,
     body: Option<String>,
     date: String,
-    key: Vec<u8>,
+    signer: &'s dyn Signer,
     method: String,
     path: String,
     userid: String,
 }
 
-impl fmt::Debug for Auth13 {
+impl<'s> fmt::Debug for Auth13<'s> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Auth13")
             .field("method", &self.method)
@@ -31,35 +31,48 @@ impl fmt::Debug for Auth13 {
     }
 }
 
-impl Auth13 {
+impl<'s> Auth13<'s> {
     pub fn new(
         path: &str,
-        key: &[u8],
+        signer: &'s dyn Signer,
         method: &str,
         userid: &str,
         api_version: &str,
         body: Option<String>,
-    ) -> Auth13 {
+    ) -> Auth13<'s> {
         let dt = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
 
         let userid: String = userid.into();
         let method = String::from(method).to_ascii_uppercase();
 
         Auth13 {
+            algorithm: SigningAlgorithm::Sha256,
             api_version: api_version.into(),
             body,
             date: dt,
-            key: key.into(),
+            signer,
             method,
-            path: squeeze_path(path),
+            path: squeeze_path(strip_query(path)),
             userid,
         }
     }
 
+    /// Sign the request as if made at `timestamp`, rather than the current time.
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Auth13<'s> {
+        self.date = timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        self
+    }
+
+    /// Set the digest used to hash and sign the request; defaults to sha256.
+    pub fn algorithm(mut self, algorithm: SigningAlgorithm) -> Auth13<'s> {
+        self.algorithm = algorithm;
+        self
+    }
+
     fn content_hash(&self) -> Result<String, Error> {
         let body = expand_string(&self.body);
         debug!("Content body is: {:?}", body);
-        let content = hash(MessageDigest::sha256(), body.as_bytes())?.to_base64(BASE64_AUTH);
+        let content = hash(self.algorithm.digest(), body.as_bytes())?.to_base64(BASE64_AUTH);
         debug!("Content hash is: {:?}", content);
         Ok(content)
     }
@@ -81,32 +94,28 @@ impl Auth13 {
     }
 
     fn signed_request(&self) -> Result<String, Error> {
-        let key = PKey::private_key_from_pem(self.key.as_slice())?;
-
         let cr = self.canonical_request()?;
-        let cr = cr.as_bytes();
-
-        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
-        signer.update(cr).unwrap();
-        let result = signer.sign_to_vec()?;
+        let result = self.signer.sign(self.algorithm, cr.as_bytes())?;
         let result = result.to_base64(BASE64_AUTH);
         debug!("base64 encoded result is {:?}", result);
         Ok(result)
     }
 
-    pub fn build(self, headers: &mut Headers) -> Result<(), Error> {
+    pub fn build(self, headers: &mut HeaderMap) -> Result<(), Error> {
         let hsh = self.content_hash()?;
-        headers.set(OpsContentHash(hsh));
-        headers.set(OpsSign(String::from("algorithm=sha256;version=1.3")));
-        headers.set(OpsTimestamp(self.date.clone()));
-        headers.set(OpsUserId(self.userid.clone()));
+        headers.insert(OPS_CONTENT_HASH, HeaderValue::from_str(&hsh)?);
+        let sign = SigningProtocol::V1_3(self.algorithm).to_string();
+        headers.insert(OPS_SIGN, HeaderValue::from_str(&sign)?);
+        headers.insert(OPS_TIMESTAMP, HeaderValue::from_str(&self.date)?);
+        headers.insert(OPS_USERID, HeaderValue::from_str(&self.userid)?);
 
         let enc = self.signed_request()?;
-        let mut i = 1;
-        for h in enc.split('\n') {
-            let key = format!("X-Ops-Authorization-{}", i);
-            headers.set_raw(key, vec![h.as_bytes().to_vec()]);
-            i += 1;
+        for (i, h) in enc.split('\n').enumerate() {
+            let key = format!("{}-{}", OPS_AUTHORIZATION, i + 1);
+            headers.insert(
+                HeaderName::from_bytes(key.as_bytes())?,
+                HeaderValue::from_str(h)?,
+            );
         }
         Ok(())
     }
@@ -115,6 +124,8 @@ impl Auth13 {
 #[cfg(test)]
 mod tests {
     use super::Auth13;
+    use crate::authentication::signer::KeySigner;
+    use crate::authentication::SigningAlgorithm;
 
     use openssl::hash::MessageDigest;
     use openssl::pkey::PKey;
@@ -138,13 +149,18 @@ mod tests {
         key.into_bytes()
     }
 
+    fn get_signer() -> KeySigner {
+        KeySigner::from_pem(&get_key_data()).unwrap()
+    }
+
     #[test]
     fn test_canonical_request() {
         let auth = Auth13 {
+            algorithm: SigningAlgorithm::Sha256,
             api_version: String::from("1"),
             body: Some(String::from(BODY)),
             date: String::from(DT),
-            key: get_key_data(),
+            signer: &get_signer(),
             method: String::from("POST"),
             path: String::from(PATH),
             userid: String::from(USER),
@@ -161,10 +177,11 @@ mod tests {
     #[test]
     fn test_signed_request() {
         let auth = Auth13 {
+            algorithm: SigningAlgorithm::Sha256,
             api_version: String::from("1"),
             body: Some(String::from(BODY)),
             date: String::from(DT),
-            key: get_key_data(),
+            signer: &get_signer(),
             method: String::from("POST"),
             path: String::from(PATH),
             userid: String::from(USER),
@@ -193,4 +210,31 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_query_excluded_from_signed_path() {
+        let signer = get_signer();
+        let auth = Auth13::new(
+            "/organizations/clownco/search/node?q=name:web*&rows=10",
+            &signer,
+            "get",
+            USER,
+            "1",
+            None,
+        );
+        assert_eq!(auth.path, "/organizations/clownco/search/node")
+    }
+
+    #[test]
+    fn test_sha1_algorithm() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        Auth13::new(PATH, &get_signer(), "post", USER, "1", Some(BODY.into()))
+            .algorithm(SigningAlgorithm::Sha1)
+            .build(&mut headers)
+            .unwrap();
+        assert_eq!(headers["x-ops-sign"], "algorithm=sha1;version=1.3");
+        assert_eq!(
+            headers["x-ops-content-hash"],
+            "DFteJZPVv6WKdQmMqZUQUumUyRs="
+        )
+    }
 }
@@ -1,7 +1,21 @@
+use crate::errors::ChefError;
+use chrono::{DateTime, Utc};
+use failure::Error;
+use openssl::hash::MessageDigest;
+use reqwest::header::HeaderMap;
 use rustc_serialize::base64::{CharacterSet, Config, Newline};
+use std::fmt;
 
+pub mod auth10;
 pub mod auth11;
 pub mod auth13;
+pub mod signer;
+pub mod verifier;
+
+use self::auth10::Auth10;
+use self::auth11::Auth11;
+use self::auth13::Auth13;
+use self::signer::Signer;
 
 pub static BASE64_AUTH: Config = Config {
     char_set: CharacterSet::Standard,
@@ -9,3 +23,153 @@ pub static BASE64_AUTH: Config = Config {
     pad: true,
     line_length: Some(60),
 };
+
+/// The digest used to hash and sign a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl SigningAlgorithm {
+    pub(crate) fn digest(self) -> MessageDigest {
+        match self {
+            SigningAlgorithm::Sha1 => MessageDigest::sha1(),
+            SigningAlgorithm::Sha256 => MessageDigest::sha256(),
+        }
+    }
+}
+
+impl fmt::Display for SigningAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SigningAlgorithm::Sha1 => write!(f, "sha1"),
+            SigningAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+/// A version of the Chef Server's request signing protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningProtocol {
+    /// Protocol 1.0, signing with sha1.
+    V1_0,
+    /// Protocol 1.1, signing with sha1.
+    V1_1,
+    /// Protocol 1.3, signing with either sha1 or sha256.
+    V1_3(SigningAlgorithm),
+}
+
+impl SigningProtocol {
+    /// Select the protocol for `version` and, optionally, a digest `algorithm`. Versions 1.0
+    /// and 1.1 only support sha1; version 1.3 defaults to sha256.
+    pub fn new(version: &str, algorithm: Option<&str>) -> Result<Self, Error> {
+        let algorithm = algorithm.map(|a| a.trim().to_ascii_lowercase());
+        match (version.trim(), algorithm.as_deref()) {
+            ("1.0", None) | ("1.0", Some("sha1")) => Ok(SigningProtocol::V1_0),
+            ("1.1", None) | ("1.1", Some("sha1")) => Ok(SigningProtocol::V1_1),
+            ("1.3", None) | ("1.3", Some("sha256")) => {
+                Ok(SigningProtocol::V1_3(SigningAlgorithm::Sha256))
+            }
+            ("1.3", Some("sha1")) => Ok(SigningProtocol::V1_3(SigningAlgorithm::Sha1)),
+            (version, algorithm) => Err(ChefError::UnsupportedSigningProtocol(
+                version.into(),
+                algorithm.unwrap_or("default").into(),
+            )
+            .into()),
+        }
+    }
+
+    /// The protocol version, such as `1.3`.
+    pub fn version(self) -> &'static str {
+        match self {
+            SigningProtocol::V1_0 => "1.0",
+            SigningProtocol::V1_1 => "1.1",
+            SigningProtocol::V1_3(_) => "1.3",
+        }
+    }
+
+    /// The digest used to hash and sign requests.
+    pub fn algorithm(self) -> SigningAlgorithm {
+        match self {
+            SigningProtocol::V1_3(algorithm) => algorithm,
+            _ => SigningAlgorithm::Sha1,
+        }
+    }
+
+    /// Sign a request with `signer`, as made at `timestamp`, adding the authentication headers
+    /// to `headers`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sign(
+        self,
+        path: &str,
+        signer: &dyn Signer,
+        method: &str,
+        userid: &str,
+        api_version: &str,
+        body: Option<String>,
+        timestamp: DateTime<Utc>,
+        headers: &mut HeaderMap,
+    ) -> Result<(), Error> {
+        match self {
+            SigningProtocol::V1_0 => Auth10::new(path, signer, method, userid, api_version, body)
+                .timestamp(timestamp)
+                .build(headers),
+            SigningProtocol::V1_1 => Auth11::new(path, signer, method, userid, api_version, body)
+                .timestamp(timestamp)
+                .build(headers),
+            SigningProtocol::V1_3(algorithm) => {
+                Auth13::new(path, signer, method, userid, api_version, body)
+                    .algorithm(algorithm)
+                    .timestamp(timestamp)
+                    .build(headers)
+            }
+        }
+    }
+}
+
+/// Formats the protocol as sent in the `X-Ops-Sign` header.
+impl fmt::Display for SigningProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "algorithm={};version={}",
+            self.algorithm(),
+            self.version()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_protocols() {
+        assert_eq!(
+            SigningProtocol::new("1.0", None).unwrap(),
+            SigningProtocol::V1_0
+        );
+        assert_eq!(
+            SigningProtocol::new("1.1", Some("SHA1")).unwrap(),
+            SigningProtocol::V1_1
+        );
+        assert_eq!(
+            SigningProtocol::new("1.3", None).unwrap().to_string(),
+            "algorithm=sha256;version=1.3"
+        );
+        assert_eq!(
+            SigningProtocol::new("1.3", Some("sha1"))
+                .unwrap()
+                .to_string(),
+            "algorithm=sha1;version=1.3"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_signing_protocols() {
+        assert!(SigningProtocol::new("1.2", None).is_err());
+        assert!(SigningProtocol::new("1.1", Some("sha256")).is_err());
+        assert!(SigningProtocol::new("1.3", Some("md5")).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::authentication::SigningAlgorithm;
+use crate::errors::ChefError;
+use failure::Error;
+use openssl::hash::hash;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Padding;
+use openssl::sign;
+use std::fmt;
+
+#[cfg(unix)]
+pub use self::agent::AgentSigner;
+
+/// The DER encoded `DigestInfo` prefixes that PKCS #1 v1.5 places before a sha1 or sha256
+/// digest when signing.
+const SHA1_DIGEST_INFO: &[u8] = &[
+    0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14,
+];
+const SHA256_DIGEST_INFO: &[u8] = &[
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// Holds an RSA private key, and signs requests with it.
+///
+/// Implement `Signer` to keep the private key outside of this process, for example in a
+/// signing agent or key management service, and pass it to `ApiClient::with_signer`.
+///
+/// Only `private_encrypt` must be implemented; the default `sign` builds a PKCS #1 v1.5
+/// signature from it.
+///
+/// `ApiClient` calls a signer on tokio's blocking thread pool, never on a runtime's own
+/// threads, so implementations may block, for example while waiting for an agent to respond.
+/// Each request waits for its signature, so a signer should still give up after a timeout.
+pub trait Signer: fmt::Debug + Send + Sync {
+    /// Apply the private key to `data`, with PKCS #1 v1.5 padding but without hashing it first,
+    /// as signing protocols 1.0 and 1.1 require.
+    fn private_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Produce a PKCS #1 v1.5 signature of `data`, hashed with `algorithm`, as signing
+    /// protocol 1.3 requires.
+    fn sign(&self, algorithm: SigningAlgorithm, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let digest_info = match algorithm {
+            SigningAlgorithm::Sha1 => SHA1_DIGEST_INFO,
+            SigningAlgorithm::Sha256 => SHA256_DIGEST_INFO,
+        };
+        let mut message = digest_info.to_vec();
+        message.extend_from_slice(&hash(algorithm.digest(), data)?);
+        self.private_encrypt(&message)
+    }
+}
+
+/// Signs requests with a private key held in memory; the default `Signer`.
+#[derive(Clone)]
+pub struct KeySigner {
+    key: PKey<Private>,
+}
+
+impl fmt::Debug for KeySigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeySigner")
+            .field("bits", &self.key.bits())
+            .finish()
+    }
+}
+
+impl KeySigner {
+    /// Create a signer from a PEM encoded RSA private key.
+    pub fn from_pem(key: &[u8]) -> Result<Self, Error> {
+        Self::from_key(key, None)
+    }
+
+    /// Create a signer from an RSA private key, which may be PEM or DER encoded, in either
+    /// PKCS #1 or PKCS #8 format. `passphrase` is required to decrypt an encrypted key.
+    pub fn from_key(key: &[u8], passphrase: Option<&[u8]>) -> Result<Self, Error> {
+        let invalid = |e: openssl::error::ErrorStack| {
+            ChefError::InvalidPrivateKeyError(match e.errors().first().and_then(|e| e.reason()) {
+                Some(reason) => reason.to_owned(),
+                None => e.to_string(),
+            })
+        };
+
+        let key = if is_pem(key) {
+            match passphrase {
+                Some(passphrase) => PKey::private_key_from_pem_passphrase(key, passphrase),
+                None if is_encrypted_pem(key) => {
+                    return Err(ChefError::EncryptedPrivateKeyError.into());
+                }
+                None => PKey::private_key_from_pem(key),
+            }
+        } else {
+            match passphrase {
+                Some(passphrase) => PKey::private_key_from_pkcs8_passphrase(key, passphrase)
+                    .or_else(|_| PKey::private_key_from_der(key)),
+                None => PKey::private_key_from_der(key),
+            }
+        }
+        .map_err(invalid)?;
+
+        if key.rsa().is_err() {
+            return Err(ChefError::InvalidPrivateKeyError("not an RSA key".into()).into());
+        }
+        Ok(Self { key })
+    }
+}
+
+fn is_pem(key: &[u8]) -> bool {
+    String::from_utf8_lossy(key)
+        .trim_start()
+        .starts_with("-----BEGIN ")
+}
+
+/// Matches both PKCS #8 `ENCRYPTED PRIVATE KEY` blocks and the `Proc-Type: 4,ENCRYPTED` header
+/// of traditional PKCS #1 keys.
+fn is_encrypted_pem(key: &[u8]) -> bool {
+    String::from_utf8_lossy(key).contains("ENCRYPTED")
+}
+
+impl Signer for KeySigner {
+    fn private_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let rsa = self.key.rsa()?;
+        let mut encrypted = vec![0; rsa.size() as usize];
+        let len = rsa.private_encrypt(data, &mut encrypted, Padding::PKCS1)?;
+        encrypted.truncate(len);
+        Ok(encrypted)
+    }
+
+    fn sign(&self, algorithm: SigningAlgorithm, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut signer = sign::Signer::new(algorithm.digest(), &self.key)?;
+        signer.update(data)?;
+        Ok(signer.sign_to_vec()?)
+    }
+}
+
+#[cfg(unix)]
+mod agent {
+    use super::Signer;
+    use crate::authentication::SigningAlgorithm;
+    use crate::errors::ChefError;
+    use failure::Error;
+    use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+    use serde_json;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// Signs requests by asking a local agent, listening on a Unix socket, to use a key that it
+    /// holds.
+    ///
+    /// Each signature is requested on a new connection, by sending a single line of JSON:
+    ///
+    /// ```json
+    /// {"operation": "private_encrypt", "key": "my-client", "data": "<base64>"}
+    /// {"operation": "sign", "algorithm": "sha256", "key": "my-client", "data": "<base64>"}
+    /// ```
+    ///
+    /// `key` is only sent if one was given to `AgentSigner::key`. The agent must respond with
+    /// a single line, either `{"signature": "<base64>"}` or `{"error": "<message>"}`.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::ApiClient;
+    /// # use chef_api::authentication::signer::AgentSigner;
+    /// # use std::sync::Arc;
+    /// let signer = AgentSigner::new("/run/chef-signer.sock").key("my-client");
+    /// let api = ApiClient::from_credentials(None)
+    ///     .unwrap()
+    ///     .with_signer(Arc::new(signer));
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct AgentSigner {
+        socket: PathBuf,
+        key: Option<String>,
+        timeout: Duration,
+    }
+
+    #[derive(Serialize)]
+    struct AgentRequest<'a> {
+        operation: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        algorithm: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        key: Option<&'a str>,
+        data: String,
+    }
+
+    #[derive(Deserialize)]
+    struct AgentResponse {
+        signature: Option<String>,
+        error: Option<String>,
+    }
+
+    impl AgentSigner {
+        /// Create a signer using the agent listening on `socket`.
+        pub fn new<P: Into<PathBuf>>(socket: P) -> Self {
+            Self {
+                socket: socket.into(),
+                key: None,
+                timeout: Duration::from_secs(10),
+            }
+        }
+
+        /// The name of the key the agent should use, if it holds more than one.
+        pub fn key(mut self, key: &str) -> Self {
+            self.key = Some(key.into());
+            self
+        }
+
+        /// How long to wait for the agent to respond; defaults to ten seconds.
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+
+        fn request(
+            &self,
+            operation: &str,
+            algorithm: Option<SigningAlgorithm>,
+            data: &[u8],
+        ) -> Result<Vec<u8>, Error> {
+            let agent_error = |e: &dyn std::fmt::Display| {
+                ChefError::SigningAgentError(format!("{}: {}", self.socket.display(), e))
+            };
+
+            let request = AgentRequest {
+                operation,
+                algorithm: algorithm.map(|a| a.to_string()),
+                key: self.key.as_deref(),
+                data: data.to_base64(STANDARD),
+            };
+            let mut request = serde_json::to_vec(&request).map_err(ChefError::JsonError)?;
+            request.push(b'\n');
+
+            let mut stream = UnixStream::connect(&self.socket).map_err(|e| agent_error(&e))?;
+            stream
+                .set_read_timeout(Some(self.timeout))
+                .and_then(|_| stream.set_write_timeout(Some(self.timeout)))
+                .and_then(|_| stream.write_all(&request))
+                .map_err(|e| agent_error(&e))?;
+
+            let mut response = String::new();
+            BufReader::new(stream)
+                .read_line(&mut response)
+                .map_err(|e| agent_error(&e))?;
+            let response: AgentResponse =
+                serde_json::from_str(&response).map_err(|e| agent_error(&e))?;
+            match (response.signature, response.error) {
+                (_, Some(error)) => Err(agent_error(&error).into()),
+                (Some(signature), None) => {
+                    signature.from_base64().map_err(|e| agent_error(&e).into())
+                }
+                (None, None) => Err(agent_error(&"no signature in response").into()),
+            }
+        }
+    }
+
+    impl Signer for AgentSigner {
+        fn private_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+            self.request("private_encrypt", None, data)
+        }
+
+        fn sign(&self, algorithm: SigningAlgorithm, data: &[u8]) -> Result<Vec<u8>, Error> {
+            self.request("sign", Some(algorithm), data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::rsa::Rsa;
+
+    fn key_signer() -> KeySigner {
+        KeySigner::from_pem(&std::fs::read("fixtures/spec-user.pem").unwrap()).unwrap()
+    }
+
+    /// Relies only on `private_encrypt`, so exercises the default `sign`.
+    #[derive(Debug)]
+    struct RawSigner(KeySigner);
+
+    impl Signer for RawSigner {
+        fn private_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+            self.0.private_encrypt(data)
+        }
+    }
+
+    #[test]
+    fn test_default_sign_matches_openssl() {
+        for algorithm in &[SigningAlgorithm::Sha1, SigningAlgorithm::Sha256] {
+            assert_eq!(
+                RawSigner(key_signer())
+                    .sign(*algorithm, b"Spec Body")
+                    .unwrap(),
+                key_signer().sign(*algorithm, b"Spec Body").unwrap()
+            )
+        }
+    }
+
+    #[test]
+    fn test_private_encrypt() {
+        let encrypted = key_signer().private_encrypt(b"Spec Body").unwrap();
+        let pem = std::fs::read("fixtures/spec-user.pem").unwrap();
+        let rsa = Rsa::private_key_from_pem(&pem).unwrap();
+        let mut decrypted = vec![0; rsa.size() as usize];
+        let len = rsa
+            .public_decrypt(&encrypted, &mut decrypted, Padding::PKCS1)
+            .unwrap();
+        assert_eq!(&decrypted[..len], b"Spec Body")
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(KeySigner::from_pem(b"not a key").is_err());
+        let ec = openssl::ec::EcKey::generate(
+            &openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1).unwrap(),
+        )
+        .unwrap();
+        let ec = PKey::from_ec_key(ec).unwrap();
+        assert!(KeySigner::from_pem(&ec.private_key_to_pem_pkcs8().unwrap()).is_err())
+    }
+
+    #[test]
+    fn test_key_formats() {
+        let key = key_signer().key;
+        let rsa = key.rsa().unwrap();
+        let expected = key_signer()
+            .sign(SigningAlgorithm::Sha256, b"Spec Body")
+            .unwrap();
+        for encoded in &[
+            key.private_key_to_pem_pkcs8().unwrap(),
+            rsa.private_key_to_der().unwrap(),
+            key.private_key_to_der().unwrap(),
+        ] {
+            let signer = KeySigner::from_key(encoded, None).unwrap();
+            assert_eq!(
+                signer.sign(SigningAlgorithm::Sha256, b"Spec Body").unwrap(),
+                expected
+            )
+        }
+    }
+
+    #[test]
+    fn test_encrypted_keys() {
+        let key = key_signer().key;
+        let cipher = openssl::symm::Cipher::aes_256_cbc();
+        let pkcs1 = key
+            .rsa()
+            .unwrap()
+            .private_key_to_pem_passphrase(cipher, b"secret")
+            .unwrap();
+        let pkcs8 = key
+            .private_key_to_pem_pkcs8_passphrase(cipher, b"secret")
+            .unwrap();
+        for encoded in &[pkcs1, pkcs8] {
+            assert!(KeySigner::from_key(encoded, Some(b"secret")).is_ok());
+            assert!(KeySigner::from_key(encoded, Some(b"wrong")).is_err());
+            match KeySigner::from_key(encoded, None)
+                .unwrap_err()
+                .downcast_ref()
+            {
+                Some(ChefError::EncryptedPrivateKeyError) => (),
+                e => panic!("expected an encrypted key error, got {:?}", e),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_agent_signer() {
+        use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
+        use serde_json::{json, Value};
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixListener;
+
+        let dir = std::env::temp_dir().join(format!("chef-api-agent-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("agent.sock");
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        std::thread::spawn(move || {
+            let signer = key_signer();
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                assert_eq!(request["key"], "spec-user");
+                let data = request["data"].as_str().unwrap().from_base64().unwrap();
+                let signature = match request["operation"].as_str().unwrap() {
+                    "sign" => signer.sign(SigningAlgorithm::Sha256, &data),
+                    _ => signer.private_encrypt(&data),
+                }
+                .unwrap();
+                let response = json!({ "signature": signature.to_base64(STANDARD) });
+                writeln!(stream, "{}", response).unwrap();
+            }
+        });
+
+        let agent = AgentSigner::new(&socket).key("spec-user");
+        assert_eq!(
+            agent.sign(SigningAlgorithm::Sha256, b"Spec Body").unwrap(),
+            key_signer()
+                .sign(SigningAlgorithm::Sha256, b"Spec Body")
+                .unwrap()
+        );
+        assert_eq!(
+            agent.private_encrypt(b"Spec Body").unwrap(),
+            key_signer().private_encrypt(b"Spec Body").unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
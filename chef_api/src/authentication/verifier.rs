@@ -0,0 +1,449 @@
+use crate::authentication::{SigningProtocol, BASE64_AUTH};
+use crate::errors::VerificationError;
+use crate::http_headers::*;
+use crate::utils::{squeeze_path, strip_query};
+use chrono::*;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::{Padding, Rsa};
+use openssl::sign::Verifier;
+use openssl::x509::X509;
+use reqwest::header::HeaderMap;
+use rustc_serialize::base64::{FromBase64, ToBase64};
+use std::fmt;
+use std::time::Duration;
+
+/// How far a request's timestamp may be from the current time, matching the Chef Server.
+pub const DEFAULT_ALLOWED_SKEW: Duration = Duration::from_secs(15 * 60);
+
+/// The API version assumed for protocol 1.3 requests without an `X-Ops-Server-API-Version`
+/// header.
+const DEFAULT_SERVER_API_VERSION: &str = "0";
+
+/// Verifies requests signed by chef-client, knife or `ApiClient`.
+///
+/// Protocol versions 1.0, 1.1 and 1.3 are supported. A request is accepted if its timestamp
+/// is within the allowed skew of the current time, its content hash matches the body, and its
+/// signature was made by the private key matching `public_key`.
+///
+/// ```rust,no_run
+/// # use chef_api::authentication::verifier::RequestVerifier;
+/// # use reqwest::header::HeaderMap;
+/// # let (public_key, headers, body) = (vec![], HeaderMap::new(), vec![]);
+/// let verifier = RequestVerifier::new(&public_key).unwrap();
+/// match verifier.verify("post", "/organizations/clownco/nodes", &headers, &body) {
+///     Ok(request) => println!("request signed by {}", request.userid),
+///     Err(e) => println!("rejected request: {}", e),
+/// }
+/// ```
+pub struct RequestVerifier {
+    key: PKey<Public>,
+    allowed_skew: Duration,
+}
+
+impl fmt::Debug for RequestVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RequestVerifier")
+            .field("allowed_skew", &self.allowed_skew)
+            .finish()
+    }
+}
+
+/// The details of a request whose signature has been verified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedRequest {
+    /// The client or user that signed the request.
+    pub userid: String,
+    /// The protocol with which the request was signed.
+    pub protocol: SigningProtocol,
+    /// When the request was signed.
+    pub timestamp: DateTime<Utc>,
+}
+
+impl RequestVerifier {
+    /// Create a verifier for requests signed by the private key matching `public_key`, which
+    /// may be a PEM encoded public key, RSA public key or X.509 certificate.
+    pub fn new(public_key: &[u8]) -> Result<Self, VerificationError> {
+        let key = Rsa::public_key_from_pem(public_key)
+            .or_else(|_| Rsa::public_key_from_pem_pkcs1(public_key))
+            .and_then(PKey::from_rsa)
+            .or_else(|_| X509::from_pem(public_key).and_then(|cert| cert.public_key()))
+            .map_err(|e| VerificationError::PublicKeyError(e.to_string()))?;
+        Ok(Self {
+            key,
+            allowed_skew: DEFAULT_ALLOWED_SKEW,
+        })
+    }
+
+    /// How far the request timestamp may be from the current time, in either direction.
+    pub fn allowed_skew(mut self, skew: Duration) -> Self {
+        self.allowed_skew = skew;
+        self
+    }
+
+    /// Verify a request. `path` may include a query string, which is not signed.
+    pub fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<VerifiedRequest, VerificationError> {
+        self.verify_at(method, path, headers, body, Utc::now())
+    }
+
+    fn verify_at(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: DateTime<Utc>,
+    ) -> Result<VerifiedRequest, VerificationError> {
+        let protocol = signing_protocol(header(headers, OPS_SIGN)?)?;
+        let userid = header(headers, OPS_USERID)?;
+        let date = header(headers, OPS_TIMESTAMP)?;
+        let content_hash = header(headers, OPS_CONTENT_HASH)?;
+        let signature = signature(headers)?;
+
+        let timestamp = DateTime::parse_from_rfc3339(date)
+            .map_err(|e| VerificationError::InvalidHeader(OPS_TIMESTAMP.into(), e.to_string()))?
+            .with_timezone(&Utc);
+        let skew = (now - timestamp).num_seconds();
+        if skew.unsigned_abs() > self.allowed_skew.as_secs() {
+            return Err(VerificationError::TimestampOutOfRange {
+                timestamp: date.into(),
+                skew,
+                allowed: self.allowed_skew.as_secs(),
+            });
+        }
+
+        let digest = protocol.algorithm().digest();
+        let body_hash = base64_digest(digest, body)?;
+        if body_hash != content_hash {
+            return Err(VerificationError::ContentHashMismatch(
+                content_hash.into(),
+                body_hash,
+            ));
+        }
+
+        let method = method.to_ascii_uppercase();
+        let path = squeeze_path(strip_query(path));
+        let verified = match protocol {
+            SigningProtocol::V1_3(_) => {
+                let api_version = match headers.get(OPS_API_VERSION) {
+                    Some(_) => header(headers, OPS_API_VERSION)?,
+                    None => DEFAULT_SERVER_API_VERSION,
+                };
+                let cr = format!(
+                    "Method:{}\nPath:{}\nX-Ops-Content-Hash:{}\n\
+                     X-Ops-Sign:version=1.3\nX-Ops-Timestamp:{}\n\
+                     X-Ops-UserId:{}\nX-Ops-Server-API-Version:{}",
+                    method, path, body_hash, date, userid, api_version
+                );
+                debug!("Canonical Request is: {:?}", cr);
+                self.verify_signature(digest, cr.as_bytes(), &signature)?
+            }
+            _ => {
+                let canonical_userid = match protocol {
+                    SigningProtocol::V1_0 => userid.to_owned(),
+                    _ => base64_digest(MessageDigest::sha1(), userid.as_bytes())?,
+                };
+                let cr = format!(
+                    "Method:{}\nHashed Path:{}\n\
+                     X-Ops-Content-Hash:{}\n\
+                     X-Ops-Timestamp:{}\nX-Ops-UserId:{}",
+                    method,
+                    base64_digest(MessageDigest::sha1(), path.as_bytes())?,
+                    body_hash,
+                    date,
+                    canonical_userid
+                );
+                debug!("Canonical Request is: {:?}", cr);
+                self.decrypt_signature(&signature)? == cr.as_bytes()
+            }
+        };
+
+        if !verified {
+            return Err(VerificationError::SignatureMismatch);
+        }
+        Ok(VerifiedRequest {
+            userid: userid.into(),
+            protocol,
+            timestamp,
+        })
+    }
+
+    fn verify_signature(
+        &self,
+        digest: MessageDigest,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, VerificationError> {
+        let mut verifier = Verifier::new(digest, &self.key).map_err(key_error)?;
+        verifier.update(data).map_err(key_error)?;
+        // OpenSSL reports a malformed signature as an error, rather than a mismatch.
+        Ok(verifier.verify(signature).unwrap_or(false))
+    }
+
+    fn decrypt_signature(&self, signature: &[u8]) -> Result<Vec<u8>, VerificationError> {
+        let rsa = self.key.rsa().map_err(key_error)?;
+        let mut decrypted = vec![0; rsa.size() as usize];
+        let len = rsa
+            .public_decrypt(signature, &mut decrypted, Padding::PKCS1)
+            .map_err(|_| VerificationError::SignatureMismatch)?;
+        decrypted.truncate(len);
+        Ok(decrypted)
+    }
+}
+
+fn key_error(err: openssl::error::ErrorStack) -> VerificationError {
+    VerificationError::PublicKeyError(err.to_string())
+}
+
+fn header<'h>(headers: &'h HeaderMap, name: &str) -> Result<&'h str, VerificationError> {
+    headers
+        .get(name)
+        .ok_or_else(|| VerificationError::MissingHeader(name.into()))?
+        .to_str()
+        .map_err(|e| VerificationError::InvalidHeader(name.into(), e.to_string()))
+}
+
+/// Parse the `X-Ops-Sign` header, such as `algorithm=sha256;version=1.3`.
+fn signing_protocol(sign: &str) -> Result<SigningProtocol, VerificationError> {
+    let mut version = None;
+    let mut algorithm = None;
+    for part in sign.split(';') {
+        match part.trim().split_once('=') {
+            Some(("version", v)) => version = Some(v.trim()),
+            Some(("algorithm", a)) => algorithm = Some(a.trim()),
+            _ => (),
+        }
+    }
+
+    let version = version.ok_or_else(|| {
+        VerificationError::InvalidHeader(OPS_SIGN.into(), format!("no version in {:?}", sign))
+    })?;
+    SigningProtocol::new(version, algorithm).map_err(|_| match version {
+        "1.0" | "1.1" | "1.3" => VerificationError::UnsupportedAlgorithm(
+            algorithm.unwrap_or_default().to_ascii_lowercase(),
+            version.into(),
+        ),
+        _ => VerificationError::UnsupportedVersion(version.into()),
+    })
+}
+
+/// Reassemble the signature from the numbered `X-Ops-Authorization-N` headers.
+fn signature(headers: &HeaderMap) -> Result<Vec<u8>, VerificationError> {
+    let mut parts = vec![];
+    for i in 1.. {
+        let name = format!("{}-{}", OPS_AUTHORIZATION, i);
+        match headers.get(name.as_str()) {
+            Some(_) => parts.push(header(headers, &name)?),
+            None => break,
+        }
+    }
+    if parts.is_empty() {
+        return Err(VerificationError::MissingHeader(format!(
+            "{}-1",
+            OPS_AUTHORIZATION
+        )));
+    }
+    parts
+        .concat()
+        .from_base64()
+        .map_err(|_| VerificationError::MalformedSignature)
+}
+
+fn base64_digest(digest: MessageDigest, data: &[u8]) -> Result<String, VerificationError> {
+    hash(digest, data)
+        .map(|h| h.to_base64(BASE64_AUTH))
+        .map_err(key_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authentication::signer::KeySigner;
+    use crate::authentication::SigningAlgorithm;
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    const PATH: &str = "/organizations/clownco/nodes";
+    const BODY: &str = "{\"name\":\"web1\"}";
+    const USER: &str = "spec-user";
+
+    fn private_key() -> Vec<u8> {
+        std::fs::read("fixtures/spec-user.pem").unwrap()
+    }
+
+    fn verifier() -> RequestVerifier {
+        let key = Rsa::private_key_from_pem(&private_key()).unwrap();
+        RequestVerifier::new(&key.public_key_to_pem().unwrap()).unwrap()
+    }
+
+    fn signed_headers(protocol: SigningProtocol) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let body = Some(String::from(BODY));
+        let signer = KeySigner::from_pem(&private_key()).unwrap();
+        protocol
+            .sign(
+                PATH,
+                &signer,
+                "post",
+                USER,
+                "1",
+                body,
+                Utc::now(),
+                &mut headers,
+            )
+            .unwrap();
+        headers.insert(OPS_API_VERSION, HeaderValue::from_static("1"));
+        headers
+    }
+
+    #[test]
+    fn test_verify_protocols() {
+        for protocol in &[
+            SigningProtocol::V1_0,
+            SigningProtocol::V1_1,
+            SigningProtocol::V1_3(SigningAlgorithm::Sha1),
+            SigningProtocol::V1_3(SigningAlgorithm::Sha256),
+        ] {
+            let request = verifier()
+                .verify("POST", PATH, &signed_headers(*protocol), BODY.as_bytes())
+                .unwrap();
+            assert_eq!(request.userid, USER);
+            assert_eq!(request.protocol, *protocol)
+        }
+    }
+
+    #[test]
+    fn test_verify_protocol_1_0_reference_signature() {
+        let date = "2009-01-01T12:00:00Z";
+        let body_hash = base64_digest(MessageDigest::sha1(), BODY.as_bytes()).unwrap();
+        let cr = format!(
+            "Method:POST\nHashed Path:{}\nX-Ops-Content-Hash:{}\n\
+             X-Ops-Timestamp:{}\nX-Ops-UserId:{}",
+            base64_digest(MessageDigest::sha1(), PATH.as_bytes()).unwrap(),
+            body_hash,
+            date,
+            USER
+        );
+        let key = Rsa::private_key_from_pem(&private_key()).unwrap();
+        let mut sig = vec![0; key.size() as usize];
+        key.private_encrypt(cr.as_bytes(), &mut sig, Padding::PKCS1)
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(OPS_SIGN, HeaderValue::from_static("version=1.0"));
+        headers.insert(OPS_USERID, HeaderValue::from_static(USER));
+        headers.insert(OPS_TIMESTAMP, HeaderValue::from_static(date));
+        headers.insert(OPS_CONTENT_HASH, HeaderValue::from_str(&body_hash).unwrap());
+        for (i, line) in sig.to_base64(BASE64_AUTH).split('\n').enumerate() {
+            let name = format!("{}-{}", OPS_AUTHORIZATION, i + 1);
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(line).unwrap(),
+            );
+        }
+
+        let now = DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc);
+        let request = verifier()
+            .verify_at("post", PATH, &headers, BODY.as_bytes(), now)
+            .unwrap();
+        assert_eq!(request.protocol, SigningProtocol::V1_0)
+    }
+
+    #[test]
+    fn test_query_is_not_signed() {
+        let path = format!("{}?start=10", PATH);
+        assert!(verifier()
+            .verify(
+                "post",
+                &path,
+                &signed_headers(SigningProtocol::V1_3(SigningAlgorithm::Sha256)),
+                BODY.as_bytes()
+            )
+            .is_ok())
+    }
+
+    #[test]
+    fn test_tampered_requests() {
+        let headers = signed_headers(SigningProtocol::V1_3(SigningAlgorithm::Sha256));
+        assert_eq!(
+            verifier()
+                .verify("post", PATH, &headers, b"{}")
+                .unwrap_err(),
+            VerificationError::ContentHashMismatch(
+                headers[OPS_CONTENT_HASH].to_str().unwrap().into(),
+                base64_digest(MessageDigest::sha256(), b"{}").unwrap()
+            )
+        );
+        assert_eq!(
+            verifier()
+                .verify("put", PATH, &headers, BODY.as_bytes())
+                .unwrap_err(),
+            VerificationError::SignatureMismatch
+        );
+        assert_eq!(
+            verifier()
+                .verify(
+                    "post",
+                    "/organizations/clownco/roles",
+                    &headers,
+                    BODY.as_bytes()
+                )
+                .unwrap_err(),
+            VerificationError::SignatureMismatch
+        )
+    }
+
+    #[test]
+    fn test_timestamp_window() {
+        let headers = signed_headers(SigningProtocol::V1_1);
+        let later = Utc::now() + chrono::Duration::minutes(20);
+        match verifier().verify_at("post", PATH, &headers, BODY.as_bytes(), later) {
+            Err(VerificationError::TimestampOutOfRange { allowed, .. }) => {
+                assert_eq!(allowed, 900)
+            }
+            res => panic!("expected a timestamp error, got {:?}", res),
+        }
+        assert!(verifier()
+            .allowed_skew(Duration::from_secs(30 * 60))
+            .verify_at("post", PATH, &headers, BODY.as_bytes(), later)
+            .is_ok())
+    }
+
+    #[test]
+    fn test_missing_and_unsupported_headers() {
+        let mut headers = signed_headers(SigningProtocol::V1_3(SigningAlgorithm::Sha256));
+        headers.remove(OPS_USERID);
+        assert_eq!(
+            verifier()
+                .verify("post", PATH, &headers, BODY.as_bytes())
+                .unwrap_err(),
+            VerificationError::MissingHeader(OPS_USERID.into())
+        );
+
+        headers.insert(OPS_SIGN, HeaderValue::from_static("version=1.2"));
+        assert_eq!(
+            verifier()
+                .verify("post", PATH, &headers, BODY.as_bytes())
+                .unwrap_err(),
+            VerificationError::UnsupportedVersion("1.2".into())
+        );
+
+        headers.insert(
+            OPS_SIGN,
+            HeaderValue::from_static("algorithm=md5;version=1.3"),
+        );
+        assert_eq!(
+            verifier()
+                .verify("post", PATH, &headers, BODY.as_bytes())
+                .unwrap_err(),
+            VerificationError::UnsupportedAlgorithm("md5".into(), "1.3".into())
+        )
+    }
+}
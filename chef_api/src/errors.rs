@@ -1,7 +1,14 @@
-use hyper;
+// failure's derive places its impls inside a named const
+#![allow(non_local_definitions)]
+
+use crate::utils::clock_offset;
 use openssl;
+use reqwest;
+use reqwest::header::HeaderMap;
 use serde_json;
+use serde_json::Value;
 use std;
+use std::fmt;
 use toml;
 use url;
 
@@ -19,29 +26,56 @@ pub enum ChefError {
         _0
     )]
     UrlParseError(#[cause] url::ParseError),
-    #[fail(
-        display = "An error occurred attempting to parse the Chef Server URL: {}",
-        _0
-    )]
-    UriError(#[cause] hyper::error::UriError),
     #[fail(display = "An error occurred communicating to the Chef Server: {}", _0)]
-    HTTPError(#[cause] hyper::error::Error),
-    #[fail(display = "An error occurred when using the API client: {}", _0)]
-    BorrowError(#[cause] std::cell::BorrowMutError),
+    HTTPError(#[cause] reqwest::Error),
     #[fail(display = "Failed to parse credentials file: {}", _0)]
     TomlDeserializeError(#[cause] toml::de::Error),
 
     // internal errors
     #[fail(display = "Failed to read private key at {}", _0)]
     PrivateKeyError(String),
+    #[fail(display = "The private key could not be used: {}", _0)]
+    InvalidPrivateKeyError(String),
+    #[fail(display = "The private key is encrypted; provide its passphrase with \
+                   Config::with_passphrase or the CHEF_CLIENT_KEY_PASSPHRASE environment variable")]
+    EncryptedPrivateKeyError,
+    #[fail(display = "Failed to load trusted certificates from {}", _0)]
+    CertificateError(String),
     #[fail(display = "Failed to interpret a list of items")]
     ListError,
     #[fail(display = "Failed to fetch {} from JSON", _0)]
     KeyMissingError(String),
     #[fail(display = "Can't read config file at {}", _0)]
     UnparseableConfigError(String),
-    #[fail(display = "Chef Server returned an error, with error code: {}", _0)]
-    ChefServerResponseError(u16),
+    #[fail(display = "{} is not set in {}", setting, source)]
+    MissingSettingError { setting: String, source: String },
+    #[fail(display = "The credentials do not include a {} profile", _0)]
+    ProfileNotFoundError(String),
+    #[fail(display = "The Chef Server URL {} can't be used: {}", _0, _1)]
+    InvalidServerUrlError(String, String),
+    #[fail(display = "Can't evaluate line {} of {}: {}", _1, _0, _2)]
+    RubyConfigError(String, usize, String),
+    #[fail(display = "Chef Server returned an error: {}", _0)]
+    ChefServerResponseError(ServerError),
+    #[fail(display = "The requested object does not exist: {}", _0)]
+    NotFoundError(ServerError),
+    #[fail(
+        display = "The object already exists or was modified concurrently: {}",
+        _0
+    )]
+    ConflictError(ServerError),
+    #[fail(display = "The Chef Server failed to authenticate the request: {}", _0)]
+    UnauthorizedError(ServerError),
+    #[fail(
+        display = "The Chef Server refused access to the requested object: {}",
+        _0
+    )]
+    ForbiddenError(ServerError),
+    #[fail(
+        display = "The Chef Server rejected the request timestamp; check the local clock: {}",
+        _0
+    )]
+    ClockSkewError(ServerError),
     #[fail(display = "Failed to deserialize JSON")]
     DeserializeError,
     #[fail(
@@ -49,4 +83,287 @@ pub enum ChefError {
         _0
     )]
     DuplicateClientNameError(String),
+    #[fail(display = "Timed out after {:?} {}", _1, _0)]
+    TimeoutError(TimeoutKind, std::time::Duration),
+    #[fail(
+        display = "Unsupported signing protocol version {} with {} algorithm",
+        _0, _1
+    )]
+    UnsupportedSigningProtocol(String, String),
+    #[fail(display = "The signing agent failed to sign the request: {}", _0)]
+    SigningAgentError(String),
+    #[fail(display = "The blocking API client can not be used: {}", _0)]
+    BlockingClientError(String),
+}
+
+impl ChefError {
+    /// Returns the details of the Chef Server's response, if this error was returned by the
+    /// server.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # use chef_api::ChefError;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// if let Err(e) = api.nodes().node("web1").get() {
+    ///     if let Some(err) = e.downcast_ref::<ChefError>().and_then(ChefError::server_error) {
+    ///         println!("{} {} failed with {}", err.method, err.path, err.status);
+    ///     }
+    /// }
+    /// ```
+    pub fn server_error(&self) -> Option<&ServerError> {
+        match *self {
+            ChefError::ChefServerResponseError(ref e)
+            | ChefError::NotFoundError(ref e)
+            | ChefError::ConflictError(ref e)
+            | ChefError::UnauthorizedError(ref e)
+            | ChefError::ForbiddenError(ref e)
+            | ChefError::ClockSkewError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The stage of a request at which a timeout elapsed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutKind {
+    /// Establishing a connection to the Chef Server.
+    Connect,
+    /// Waiting for the Chef Server to send a response, or the next part of one.
+    Read,
+    /// Completing the whole request, including any retries.
+    Total,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimeoutKind::Connect => write!(f, "connecting to the Chef Server"),
+            TimeoutKind::Read => write!(f, "waiting for the Chef Server to respond"),
+            TimeoutKind::Total => write!(f, "waiting for the request to complete"),
+        }
+    }
+}
+
+/// The reason a signed request failed verification.
+#[derive(Fail, Debug, Clone, PartialEq)]
+pub enum VerificationError {
+    #[fail(display = "The request is missing the {} header", _0)]
+    MissingHeader(String),
+    #[fail(display = "The {} header is not valid: {}", _0, _1)]
+    InvalidHeader(String, String),
+    #[fail(display = "Unsupported signing protocol version {}", _0)]
+    UnsupportedVersion(String),
+    #[fail(
+        display = "Unsupported signing algorithm {} for protocol version {}",
+        _0, _1
+    )]
+    UnsupportedAlgorithm(String, String),
+    #[fail(
+        display = "The request timestamp {} is {}s from the current time, outside the allowed {}s",
+        timestamp, skew, allowed
+    )]
+    TimestampOutOfRange {
+        timestamp: String,
+        skew: i64,
+        allowed: u64,
+    },
+    #[fail(
+        display = "The content hash {} does not match the request body, which hashes to {}",
+        _0, _1
+    )]
+    ContentHashMismatch(String, String),
+    #[fail(display = "The signature is not valid base64")]
+    MalformedSignature,
+    #[fail(display = "The signature does not match the request")]
+    SignatureMismatch,
+    #[fail(display = "The public key could not be used: {}", _0)]
+    PublicKeyError(String),
+}
+
+/// The Chef Server rejects requests signed more than 15 minutes from its own clock.
+const MAX_CLOCK_SKEW_SECS: i64 = 15 * 60;
+
+/// A failed response from the Chef Server.
+#[derive(Debug, Clone)]
+pub struct ServerError {
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// The HTTP method of the request.
+    pub method: String,
+    /// The path and query of the request.
+    pub path: String,
+    /// The error messages returned by the server.
+    pub messages: Vec<String>,
+    /// The headers of the response.
+    pub headers: HeaderMap,
+    /// How far the Chef Server's clock is ahead of the local clock, according to the `Date`
+    /// header of the response.
+    pub clock_offset: Option<chrono::Duration>,
+}
+
+impl ServerError {
+    /// Build a `ServerError`, extracting the messages from the response body. The Chef Server
+    /// usually returns a JSON object with an `error` list, but proxies in front of it may not.
+    pub fn new(status: u16, method: &str, path: &str, headers: HeaderMap, body: &[u8]) -> Self {
+        Self {
+            status,
+            method: method.to_ascii_uppercase(),
+            path: path.into(),
+            messages: error_messages(body),
+            clock_offset: clock_offset(&headers),
+            headers,
+        }
+    }
+
+    /// Returns true if the server rejected the request because the local clock differs too
+    /// far from the server's.
+    pub fn is_clock_skew(&self) -> bool {
+        let skewed = self
+            .clock_offset
+            .is_some_and(|offset| offset.num_seconds().abs() > MAX_CLOCK_SKEW_SECS);
+        self.status == 401
+            && (skewed
+                || self
+                    .messages
+                    .iter()
+                    .any(|m| m.contains("Synchronize the clock")))
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} returned {}", self.method, self.path, self.status)?;
+        if !self.messages.is_empty() {
+            write!(f, ": {}", self.messages.join("; "))?;
+        }
+        match self.clock_offset {
+            Some(offset) if self.is_clock_skew() => {
+                let direction = if offset.num_seconds() > 0 {
+                    "behind"
+                } else {
+                    "ahead of"
+                };
+                write!(
+                    f,
+                    " (the local clock is {}s {} the Chef Server)",
+                    offset.num_seconds().abs(),
+                    direction
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<ServerError> for ChefError {
+    fn from(err: ServerError) -> Self {
+        match err.status {
+            401 if err.is_clock_skew() => ChefError::ClockSkewError(err),
+            401 => ChefError::UnauthorizedError(err),
+            403 => ChefError::ForbiddenError(err),
+            404 => ChefError::NotFoundError(err),
+            409 => ChefError::ConflictError(err),
+            _ => ChefError::ChefServerResponseError(err),
+        }
+    }
+}
+
+fn error_messages(body: &[u8]) -> Vec<String> {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(ref json) => match json.get("error") {
+            Some(Value::Array(errors)) => errors
+                .iter()
+                .map(|e| match e.as_str() {
+                    Some(e) => e.to_owned(),
+                    None => e.to_string(),
+                })
+                .collect(),
+            Some(Value::String(error)) => vec![error.clone()],
+            Some(error) => vec![error.to_string()],
+            None => vec![json.to_string()],
+        },
+        Err(_) => {
+            let body = String::from_utf8_lossy(body);
+            let body = body.trim();
+            if body.is_empty() {
+                vec![]
+            } else {
+                vec![body.to_owned()]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(status: u16, body: &str) -> ServerError {
+        ServerError::new(
+            status,
+            "get",
+            "/organizations/clownco/nodes/web1",
+            HeaderMap::new(),
+            body.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let err = server_error(
+            400,
+            r#"{"error":["Field 'name' invalid","Field 'run_list' invalid"]}"#,
+        );
+        assert_eq!(
+            err.messages,
+            vec!["Field 'name' invalid", "Field 'run_list' invalid"]
+        );
+        assert_eq!(
+            err.to_string(),
+            "GET /organizations/clownco/nodes/web1 returned 400: \
+             Field 'name' invalid; Field 'run_list' invalid"
+        )
+    }
+
+    #[test]
+    fn test_error_messages_from_non_json_body() {
+        let err = server_error(502, "<html>Bad Gateway</html>\n");
+        assert_eq!(err.messages, vec!["<html>Bad Gateway</html>"]);
+        assert!(server_error(500, "").messages.is_empty())
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let not_found: ChefError =
+            server_error(404, r#"{"error":["Cannot load node web1"]}"#).into();
+        assert!(matches!(not_found, ChefError::NotFoundError(_)));
+
+        let skew: ChefError = server_error(
+            401,
+            r#"{"error":["Failed to authenticate as spec-user. Synchronize the clock on your host."]}"#,
+        )
+        .into();
+        assert!(matches!(skew, ChefError::ClockSkewError(_)));
+
+        let unauthorized: ChefError =
+            server_error(401, r#"{"error":["Invalid signature"]}"#).into();
+        assert!(matches!(unauthorized, ChefError::UnauthorizedError(_)));
+        assert_eq!(unauthorized.server_error().unwrap().status, 401)
+    }
+
+    #[test]
+    fn test_clock_skew_from_date_header() {
+        let mut headers = HeaderMap::new();
+        let date = (chrono::Utc::now() - chrono::Duration::minutes(30)).to_rfc2822();
+        headers.insert(
+            reqwest::header::DATE,
+            reqwest::header::HeaderValue::from_str(&date).unwrap(),
+        );
+        let err = ServerError::new(401, "get", "/nodes", headers, b"");
+        assert!(err.is_clock_skew());
+        assert!(err
+            .to_string()
+            .contains("the local clock is 1800s ahead of the Chef Server"));
+        assert!(matches!(ChefError::from(err), ChefError::ClockSkewError(_)))
+    }
 }
@@ -1,21 +1,11 @@
-header! {
-    (OpsSign, "X-Ops-Sign") => [String]
-}
-header! {
-    (OpsUserId, "X-Ops-Userid") => [String]
-}
-header! {
-    (OpsTimestamp, "X-Ops-Timestamp") => [String]
-}
-header! {
-    (OpsContentHash, "X-Ops-Content-Hash") => [String]
-}
-header! {
-    (OpsApiVersion, "X-Ops-Server-API-Version") => [u8]
-}
-header! {
-    (OpsApiInfo, "X-Ops-Server-API-Info") => [u8]
-}
-header! {
-    (ChefVersion, "X-Chef-Version") => [String]
-}
+pub const OPS_SIGN: &str = "x-ops-sign";
+pub const OPS_USERID: &str = "x-ops-userid";
+pub const OPS_TIMESTAMP: &str = "x-ops-timestamp";
+pub const OPS_CONTENT_HASH: &str = "x-ops-content-hash";
+pub const OPS_API_VERSION: &str = "x-ops-server-api-version";
+pub const OPS_API_INFO: &str = "x-ops-server-api-info";
+pub const CHEF_VERSION: &str = "x-chef-version";
+
+/// The prefix of the headers carrying the request signature, which is split over as many
+/// `X-Ops-Authorization-N` headers as necessary.
+pub const OPS_AUTHORIZATION: &str = "x-ops-authorization";
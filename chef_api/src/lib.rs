@@ -20,12 +20,42 @@
 //! let cookbooks = client.cookbooks().get();
 //! ```
 //!
+//! Workstations that still configure knife with a `knife.rb` or `config.rb` can load the static
+//! settings from it instead:
+//!
+//! ```rust,no_run
+//! use chef_api::api_client::ApiClient;
+//! use chef_api::credentials::Config;
+//!
+//! let client = ApiClient::new(Config::from_knife().unwrap()).unwrap();
+//! ```
+//!
+//! ## Asynchronous requests
+//!
+//! `ApiClient` is `Send` and `Sync`, and every request builder also implements `ExecuteAsync`,
+//! returning futures that can be awaited or spawned on a [tokio] runtime:
+//!
+//! ```rust,no_run
+//! use chef_api::api_client::{ApiClient, ExecuteAsync};
+//!
+//! # async fn run() -> Result<(), failure::Error> {
+//! let client = ApiClient::from_credentials(None)?;
+//! let cookbooks = client.cookbooks().get_async().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The blocking `Execute` methods may also be used on the threads of a multi-threaded tokio
+//! runtime, and in `spawn_blocking` closures. On a single-threaded runtime they return a
+//! `BlockingClientError`.
+//!
 //! This crate uses [`serde`] to serialize requests from JSON.
 //!
 //! [Chef]: https://www.chef.io/chef/
 //! [Chef Server API]: https://chef-server-api-docs.chef.io/
 //! [RFC 99]: https://chef.github.io/chef-rfc/rfc099-authentication-config-file.html
 //! [`serde`]: https://serde.rs/
+//! [tokio]: https://tokio.rs/
 //!
 
 #[macro_use]
@@ -33,15 +63,14 @@ extern crate failure;
 
 extern crate chrono;
 extern crate openssl;
+extern crate rand;
 extern crate rustc_serialize;
 extern crate url;
 
 extern crate futures;
-
-#[macro_use]
-extern crate hyper;
-extern crate hyper_openssl;
-extern crate tokio_core;
+extern crate reqwest;
+extern crate tokio;
+extern crate tower;
 
 #[macro_use]
 extern crate log;
@@ -52,6 +81,7 @@ extern crate serde_derive;
 extern crate serde_json;
 
 extern crate toml;
+extern crate toml_edit;
 
 extern crate dirs;
 
@@ -62,6 +92,11 @@ mod http_headers;
 #[macro_use]
 mod macros;
 pub mod credentials;
+pub mod retry;
+mod ruby_config;
+#[cfg(test)]
+mod test_support;
+pub mod typed;
 pub mod utils;
 
 pub mod api_client;
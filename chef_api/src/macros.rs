@@ -1,37 +1,20 @@
 macro_rules! build {
     ($name:ident, $type:ident) => {
-        #[doc="Generate a new $type request."]
-        pub fn $name(&self) -> $type {
+        #[doc = "Generate a new $type request."]
+        pub fn $name(&self) -> $type<'_> {
             self.into()
         }
-    }
+    };
 }
 
 macro_rules! import {
     () => {
         use failure::Error;
         use $crate::api_client::*;
-        use $crate::authentication::auth11::Auth11;
-        use $crate::authentication::auth13::Auth13;
-        use $crate::credentials::Config;
-        use $crate::http_headers::*;
         use $crate::utils::add_path_element;
 
         use serde::Serialize;
         use serde_json;
-
-        use std::cell::RefCell;
-        use std::rc::Rc;
-
-        use futures::{Future, Stream};
-        use tokio_core::reactor::Core;
-
-        use hyper::client::HttpConnector;
-        use hyper::header::{qitem, Accept, ContentLength, ContentType};
-        use hyper::mime::APPLICATION_JSON;
-        use hyper::Client as HyperClient;
-        use hyper::{Method, Request};
-        use hyper_openssl::HttpsConnector;
     };
 }
 
@@ -69,6 +52,18 @@ macro_rules! path {
     };
 }
 
+macro_rules! param {
+    (
+        $(#[$outer:meta])*
+        $n:ident
+    ) => {
+        $(#[$outer])*
+        pub fn $n<V: ToString>(&mut self, value: V) -> &mut Self {
+            self.query_param(stringify!($n), &value.to_string())
+        }
+    };
+}
+
 macro_rules! acls {
     () => {
         /// Get the list of ACLs on this object
@@ -82,18 +77,18 @@ macro_rules! acls {
             self.path = add_path_element(self.path.clone(), permission);
             self
         }
-    }
+    };
 }
 
 macro_rules! request_type {
     ($n:ident) => {
         #[derive(Debug, Clone)]
         pub struct $n<'c> {
-            pub(crate) client: &'c Rc<HyperClient<HttpsConnector<HttpConnector>>>,
-            pub(crate) core: &'c Rc<RefCell<Core>>,
-            pub(crate) config: &'c Config,
+            pub(crate) api: &'c ApiClient,
             pub(crate) path: String,
             pub(crate) api_version: String,
+            pub(crate) query: Vec<(String, String)>,
+            pub(crate) timeouts: Timeouts,
         }
     };
 }
@@ -106,11 +101,11 @@ macro_rules! requests {
             fn from(api: &'c ApiClient) -> Self {
                 let path = String::from("/");
                 Self {
-                    config: &api.config,
-                    client: &api.client,
-                    core: &api.core,
+                    api,
                     path,
                     api_version: String::from("1"),
+                    query: vec![],
+                    timeouts: api.timeouts,
                 }
             }
         }
@@ -122,13 +117,13 @@ macro_rules! requests {
 
         impl<'c> From<&'c ApiClient> for $n<'c> {
             fn from(api: &'c ApiClient) -> Self {
-                let path = add_path_element(String::from("/"), stringify!($p));
+                let path = add_path_element(String::new(), stringify!($p));
                 Self {
-                    config: &api.config,
-                    client: &api.client,
-                    core: &api.core,
+                    api,
                     path,
                     api_version: String::from("1"),
+                    query: vec![],
+                    timeouts: api.timeouts,
                 }
             }
         }
@@ -140,14 +135,17 @@ macro_rules! requests {
 
         impl<'c> From<&'c ApiClient> for $n<'c> {
             fn from(api: &'c ApiClient) -> Self {
-                let path =
-                    add_path_element(api.config.organization_path().unwrap(), stringify!($p));
+                // An unparseable chef_server_url is reported when the request is executed.
+                let path = add_path_element(
+                    api.config.organization_path().unwrap_or_default(),
+                    stringify!($p),
+                );
                 Self {
-                    config: &api.config,
-                    client: &api.client,
-                    core: &api.core,
+                    api,
                     path,
                     api_version: String::from("1"),
+                    query: vec![],
+                    timeouts: api.timeouts,
                 }
             }
         }
@@ -159,102 +157,73 @@ macro_rules! requests {
 macro_rules! execute {
     ($n:ident) => {
         use serde_json::Value;
+        #[allow(unused_imports)]
         use $crate::errors::ChefError;
 
+        impl<'e> $n<'e> {
+            pub(crate) fn to_request(&self, method: &str, body: String) -> ChefRequest {
+                ChefRequest {
+                    config: self.api.config.clone(),
+                    method: method.into(),
+                    path: self.path.clone(),
+                    query: self.query.clone(),
+                    api_version: self.api_version.clone(),
+                    body,
+                    retry_policy: self.api.retry_policy.clone(),
+                    timeouts: self.timeouts,
+                    signer: self.api.signer.clone(),
+                    clock: self.api.clock.clone(),
+                }
+            }
+        }
+
         impl<'e> Execute for $n<'e> {
             fn api_version(&mut self, api_version: &str) -> &mut Self {
                 self.api_version = api_version.into();
                 self
             }
 
+            fn query_param(&mut self, key: &str, value: &str) -> &mut Self {
+                self.query.retain(|(k, _)| k != key);
+                self.query.push((key.into(), value.into()));
+                self
+            }
+
+            fn timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+                self.timeouts.total = Some(timeout);
+                self
+            }
+
+            fn read_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+                self.timeouts.read = Some(timeout);
+                self
+            }
+
+            fn connect_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+                self.timeouts.connect = Some(timeout);
+                self
+            }
+
             #[doc(hidden)]
             fn execute<B>(&self, body: Option<B>, method: &str) -> Result<Value, Error>
             where
                 B: Serialize,
             {
-                let userid = self.config.client_name()?;
-                let key = self.config.key()?;
-                let sign_ver = self.config.sign_ver.clone();
-                let path = self.path.clone();
-                let api_version = self.api_version.clone();
-
-                let url = format!("{}{}", &self.config.url_base()?, path).parse()?;
-
-                let mth = match method {
-                    "put" => Method::Put,
-                    "post" => Method::Post,
-                    "delete" => Method::Delete,
-                    "head" => Method::Head,
-                    _ => Method::Get,
-                };
-
-                let mut request = Request::new(mth, url);
-
-                let body = match body {
-                    Some(b) => serde_json::to_string(&b)?,
-                    None => serde_json::to_string("")?,
-                };
-
-                match sign_ver.as_str() {
-                    "1.1" => Auth11::new(
-                        &path,
-                        &key,
-                        method,
-                        &userid,
-                        &api_version,
-                        Some(body.clone().into()),
-                    )
-                    .build(request.headers_mut())?,
-                    _ => Auth13::new(
-                        &path,
-                        &key,
-                        method,
-                        &userid,
-                        &api_version,
-                        Some(body.clone().into()),
-                    )
-                    .build(request.headers_mut())?,
-                };
-
-                let json = APPLICATION_JSON;
-                request.headers_mut().set(Accept(vec![qitem(json.clone())]));
-                request.headers_mut().set(ContentType::json());
-                request
-                    .headers_mut()
-                    .set(ContentLength(body.clone().len() as u64));
-                request.headers_mut().set(OpsApiInfo(1));
-                request.headers_mut().set(OpsApiVersion(1));
-                request
-                    .headers_mut()
-                    .set(ChefVersion(String::from("13.3.34")));
-
-                request.set_body(body);
-
-                let client = self.client;
-                let resp = client
-                    .request(request)
-                    .map_err(ChefError::HTTPError)
-                    .and_then(|res| {
-                        debug!("Status is {:?}", res.status());
-
-                        let status = res.status();
-                        res.body()
-                            .concat2()
-                            .map_err(ChefError::HTTPError)
-                            .and_then(move |body| {
-                                let body: Value =
-                                    serde_json::from_slice(&body).map_err(ChefError::JsonError)?;
-
-                                if status.is_success() {
-                                    Ok(body)
-                                } else {
-                                    Err(ChefError::ChefServerResponseError(status.as_u16()))
-                                }
-                            })
-                    });
+                let body = serialize_body(body)?;
+                self.api.execute_blocking(self.to_request(method, body))
+            }
+        }
 
-                let mut core = self.core.try_borrow_mut()?;
-                core.run(resp).map_err(|e| e.into())
+        impl<'e> ExecuteAsync for $n<'e> {
+            #[doc(hidden)]
+            fn execute_async<B>(&self, body: Option<B>, method: &str) -> ResponseFuture
+            where
+                B: Serialize,
+            {
+                match serialize_body(body) {
+                    Ok(body) => self.api.execute_async(self.to_request(method, body)),
+                    Err(e) => Box::pin(std::future::ready(Err(e))),
+                }
             }
         }
     };
@@ -6,5 +6,9 @@ impl<'c> CookbooksQuery<'c> {
     path!(-> recipes = "_recipes");
     path!(cookbook);
     path!(version);
+    param!(
+        /// The number of versions of each cookbook to return; use `all` for every version.
+        num_versions
+    );
     acls!();
 }
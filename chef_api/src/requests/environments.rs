@@ -10,6 +10,10 @@ impl<'c> EnvironmentsQuery<'c> {
     path!(-> recipes);
     path!(-> roles);
     path!(role);
+    param!(
+        /// The number of cookbook versions to return; use `all` for every version.
+        num_versions
+    );
 
     acls!();
 }
@@ -1,6 +1,367 @@
 import!();
 requests!(SearchQuery, search);
 
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, VecDeque};
+
+/// The number of rows the Chef Server returns per page when `rows` is not specified.
+pub const DEFAULT_SEARCH_ROWS: u64 = 1000;
+
+/// A single page of search results.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchResult<T = Value> {
+    /// The total number of objects matching the query.
+    pub total: u64,
+    /// The offset of the first object in this page.
+    pub start: u64,
+    /// The objects in this page.
+    pub rows: Vec<T>,
+}
+
+/// A single row of partial search results.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartialSearchRow<T = Value> {
+    /// The URL of the matching object.
+    pub url: String,
+    /// The requested attributes of the matching object, keyed by result name.
+    pub data: T,
+}
+
+/// The attributes to return from a partial search, mapping result keys to attribute paths.
+///
+/// ```rust
+/// # use chef_api::requests::PartialSearchFilter;
+/// let filter = PartialSearchFilter::new()
+///     .key("name", &["name"])
+///     .key("kernel_release", &["kernel", "release"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PartialSearchFilter(BTreeMap<String, Vec<String>>);
+
+impl PartialSearchFilter {
+    /// Create an empty filter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the attribute found at `path` in the results, under the name `key`.
+    pub fn key(mut self, key: &str, path: &[&str]) -> Self {
+        self.0
+            .insert(key.into(), path.iter().map(|&p| p.into()).collect());
+        self
+    }
+}
+
 impl<'c> SearchQuery<'c> {
-    path!(search_index);
+    path!(
+        /// The index to search; one of `client`, `environment`, `node`, `role` or the name of a
+        /// data bag.
+        search_index
+    );
+    param!(
+        /// The search query, using Solr query syntax.
+        q
+    );
+    param!(
+        /// The number of rows to return.
+        rows
+    );
+    param!(
+        /// The row at which to start returning results.
+        start
+    );
+    param!(
+        /// How to order the results.
+        sort
+    );
+
+    /// Run the search, returning a single page of results.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # use serde_json::Value;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// let page = api.search().search_index("node").q("role:web").results::<Value>().unwrap();
+    /// println!("found {} nodes", page.total);
+    /// ```
+    pub fn results<T: DeserializeOwned>(&self) -> Result<SearchResult<T>, Error> {
+        self.page(None)
+    }
+
+    /// Run a partial search, returning a single page of results containing only the
+    /// attributes named in `filter`.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # use chef_api::requests::PartialSearchFilter;
+    /// # use serde_json::Value;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// let filter = PartialSearchFilter::new().key("ip", &["ipaddress"]);
+    /// let page = api
+    ///     .search()
+    ///     .search_index("node")
+    ///     .q("role:web")
+    ///     .partial_results::<Value>(&filter)
+    ///     .unwrap();
+    /// for row in page.rows {
+    ///     println!("{} has address {}", row.url, row.data["ip"]);
+    /// }
+    /// ```
+    pub fn partial_results<T: DeserializeOwned>(
+        &self,
+        filter: &PartialSearchFilter,
+    ) -> Result<SearchResult<PartialSearchRow<T>>, Error> {
+        self.page(Some(filter))
+    }
+
+    /// Iterate over every result of the search, requesting further pages from the Chef Server
+    /// as required. `rows` sets the page size, and `start` the first row returned.
+    ///
+    /// ```rust,no_run
+    /// # use chef_api::api_client::*;
+    /// # use serde_json::Value;
+    /// # let api = ApiClient::from_credentials(None).unwrap();
+    /// for node in api.search().search_index("node").q("role:web").iter::<Value>() {
+    ///     println!("{}", node.unwrap()["name"]);
+    /// }
+    /// ```
+    pub fn iter<T: DeserializeOwned>(&self) -> SearchIter<'c, T> {
+        self.paginate(None)
+    }
+
+    /// Iterate over every result of a partial search, requesting further pages from the Chef
+    /// Server as required.
+    pub fn partial_iter<T: DeserializeOwned>(
+        &self,
+        filter: &PartialSearchFilter,
+    ) -> SearchIter<'c, PartialSearchRow<T>> {
+        self.paginate(Some(filter.clone()))
+    }
+
+    fn page<T: DeserializeOwned>(
+        &self,
+        filter: Option<&PartialSearchFilter>,
+    ) -> Result<SearchResult<T>, Error> {
+        let page = match filter {
+            Some(filter) => self.post(filter)?,
+            None => self.get()?,
+        };
+        deserialize_response(page)
+    }
+
+    fn paginate<T: DeserializeOwned>(
+        &self,
+        filter: Option<PartialSearchFilter>,
+    ) -> SearchIter<'c, T> {
+        SearchIter {
+            search: self.clone(),
+            start: self.numeric_param("start").unwrap_or(0),
+            rows: self
+                .numeric_param("rows")
+                .filter(|&r| r > 0)
+                .unwrap_or(DEFAULT_SEARCH_ROWS),
+            filter,
+            total: None,
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn numeric_param(&self, key: &str) -> Option<u64> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.parse().ok())
+    }
+}
+
+/// An iterator over all of the results of a search, fetching pages lazily.
+///
+/// Iteration stops after the first error is returned.
+#[derive(Debug)]
+pub struct SearchIter<'c, T> {
+    search: SearchQuery<'c>,
+    start: u64,
+    rows: u64,
+    filter: Option<PartialSearchFilter>,
+    total: Option<u64>,
+    buffer: VecDeque<T>,
+    done: bool,
+}
+
+impl<'c, T> SearchIter<'c, T> {
+    /// The total number of results matching the query, once the first page has been fetched.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+}
+
+impl<'c, T: DeserializeOwned> Iterator for SearchIter<'c, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                return Some(Ok(item));
+            }
+            if self.done || self.total.is_some_and(|total| self.start >= total) {
+                return None;
+            }
+
+            self.search.start(self.start).rows(self.rows);
+            let page = self.search.page::<T>(self.filter.as_ref());
+            match page {
+                Ok(page) => {
+                    debug!(
+                        "Fetched {} search results from {} of {}",
+                        page.rows.len(),
+                        page.start,
+                        page.total
+                    );
+                    self.total = Some(page.total);
+                    // The server may drop rows for objects deleted since they were indexed, so
+                    // always advance by the requested page size.
+                    self.start += self.rows;
+                    self.buffer.extend(page.rows);
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use serde_json::json;
+
+    fn page(total: u64, start: u64, names: &[&str]) -> String {
+        let rows: Vec<_> = names.iter().map(|name| json!({ "name": name })).collect();
+        json_response(&json!({ "total": total, "start": start, "rows": rows }).to_string())
+    }
+
+    fn request_line(request: &str) -> &str {
+        request.lines().next().unwrap_or("")
+    }
+
+    #[test]
+    fn test_search_iter() {
+        let (url, requests) = serve(vec![
+            page(5, 0, &["web1", "web2"]),
+            page(5, 2, &["web3", "web4"]),
+            page(5, 4, &["web5"]),
+        ]);
+        let api = api_client(&url);
+        let mut search = api.search();
+        search.search_index("node").q("role:web").rows(2);
+        let mut iter = search.iter::<Value>();
+        assert_eq!(iter.total(), None);
+
+        let names: Vec<String> = iter
+            .by_ref()
+            .map(|node| node.unwrap()["name"].as_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, vec!["web1", "web2", "web3", "web4", "web5"]);
+        assert_eq!(iter.total(), Some(5));
+
+        let lines: Vec<String> = requests
+            .try_iter()
+            .map(|r| request_line(&r).to_owned())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "GET /organizations/bedrock/search/node?q=role%3Aweb&start=0&rows=2 HTTP/1.1",
+                "GET /organizations/bedrock/search/node?q=role%3Aweb&start=2&rows=2 HTTP/1.1",
+                "GET /organizations/bedrock/search/node?q=role%3Aweb&start=4&rows=2 HTTP/1.1",
+            ]
+        )
+    }
+
+    #[test]
+    fn test_search_iter_stops_after_error() {
+        let (url, requests) = serve(vec![
+            page(5, 0, &["web1", "web2"]),
+            String::from("HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n"),
+        ]);
+        let api = api_client(&url);
+        let mut search = api.search();
+        search.search_index("node").rows(2);
+        let results: Vec<_> = search.iter::<Value>().collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok() && results[1].is_ok());
+        assert!(results[2].is_err());
+        assert_eq!(requests.try_iter().count(), 2)
+    }
+
+    #[test]
+    fn test_partial_iter() {
+        let row = |name: &str, ip: &str| {
+            json!({
+                "url": format!("https://chef.local/organizations/bedrock/nodes/{}", name),
+                "data": { "ip": ip },
+            })
+        };
+        let (url, requests) = serve(vec![
+            json_response(
+                &json!({ "total": 3, "start": 0, "rows": [row("web1", "10.0.0.1"), row("web2", "10.0.0.2")] })
+                    .to_string(),
+            ),
+            json_response(
+                &json!({ "total": 3, "start": 2, "rows": [row("web3", "10.0.0.3")] }).to_string(),
+            ),
+        ]);
+        let api = api_client(&url);
+        let filter = PartialSearchFilter::new().key("ip", &["ipaddress"]);
+        let mut search = api.search();
+        search.search_index("node").q("role:web").rows(2);
+        let rows: Vec<PartialSearchRow<BTreeMap<String, String>>> = search
+            .partial_iter(&filter)
+            .map(|row| row.unwrap())
+            .collect();
+        let ips: Vec<&str> = rows.iter().map(|row| row.data["ip"].as_str()).collect();
+        assert_eq!(ips, vec!["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+        assert_eq!(
+            rows[2].url,
+            "https://chef.local/organizations/bedrock/nodes/web3"
+        );
+
+        let requests: Vec<String> = requests.try_iter().collect();
+        assert_eq!(requests.len(), 2);
+        for (start, request) in [0, 2].iter().zip(requests) {
+            assert_eq!(
+                request_line(&request),
+                format!(
+                    "POST /organizations/bedrock/search/node?q=role%3Aweb&start={}&rows=2 HTTP/1.1",
+                    start
+                )
+            );
+            assert_eq!(request_body(&request), r#"{"ip":["ipaddress"]}"#);
+        }
+    }
+
+    #[test]
+    fn test_partial_search_filter() {
+        let filter = PartialSearchFilter::new()
+            .key("name", &["name"])
+            .key("kernel_release", &["kernel", "release"]);
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"kernel_release":["kernel","release"],"name":["name"]}"#
+        )
+    }
+
+    #[test]
+    fn test_partial_search_result() {
+        let page = r#"{"total":1,"start":0,"rows":[
+            {"url":"https://chef.local/organizations/clownco/nodes/web1","data":{"ip":"10.0.0.1"}}
+        ]}"#;
+        let page: SearchResult<PartialSearchRow> = serde_json::from_str(page).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.rows[0].data["ip"], "10.0.0.1")
+    }
 }
@@ -0,0 +1,242 @@
+use crate::errors::{ChefError, TimeoutKind};
+
+use chrono::{DateTime, Utc};
+use failure::Error;
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::Duration;
+
+/// Describes when, and how quickly, failed requests are retried.
+///
+/// Each retry is signed afresh, so the Chef Server sees a current `X-Ops-Timestamp`. The delay
+/// before each retry doubles from `initial_backoff` up to `max_backoff`, with random jitter
+/// applied so that many clients do not retry in lockstep.
+///
+/// The default policy makes a single attempt; use `RetryPolicy::new` to enable retries.
+///
+/// ```rust,no_run
+/// # use chef_api::api_client::ApiClient;
+/// # use chef_api::retry::RetryPolicy;
+/// # use std::time::Duration;
+/// let policy = RetryPolicy::new(5).initial_backoff(Duration::from_secs(1));
+/// let api = ApiClient::from_credentials(None).unwrap().with_retry_policy(policy);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    statuses: Vec<u16>,
+    methods: Vec<String>,
+    retry_connection_errors: bool,
+    respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            statuses: vec![502, 503, 504],
+            methods: vec!["get".into(), "head".into(), "put".into(), "delete".into()],
+            retry_connection_errors: true,
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy making up to `max_attempts` attempts at each request. By default only
+    /// idempotent methods are retried, after connection failures or a 502, 503 or 504 response.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// A policy that never retries a request.
+    pub fn never() -> Self {
+        Self::default()
+    }
+
+    /// The maximum number of attempts made, including the first.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The delay before the first retry.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The longest delay between attempts, including any requested with `Retry-After`.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Whether to randomise each delay to between half and all of the calculated backoff.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The HTTP status codes that cause a request to be retried.
+    pub fn statuses(mut self, statuses: &[u16]) -> Self {
+        self.statuses = statuses.into();
+        self
+    }
+
+    /// The HTTP methods that may be retried, such as `get` or `post`.
+    pub fn methods(mut self, methods: &[&str]) -> Self {
+        self.methods = methods.iter().map(|m| m.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Whether to retry requests that failed to connect, whose connection was reset, or that
+    /// timed out waiting for a connection or response.
+    pub fn retry_connection_errors(mut self, retry: bool) -> Self {
+        self.retry_connection_errors = retry;
+        self
+    }
+
+    /// Whether to wait for the period requested by a `Retry-After` header, when present.
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    /// Returns how long to wait before retrying a request that failed with `err`, or `None` if
+    /// the request should not be retried. `attempt` is the number of attempts made so far.
+    pub(crate) fn retry_delay(&self, attempt: u32, method: &str, err: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.methods.iter().any(|m| m == method) {
+            return None;
+        }
+
+        let retry_after = match err.downcast_ref::<ChefError>() {
+            Some(ChefError::HTTPError(e))
+                if self.retry_connection_errors
+                    && (e.is_connect() || e.is_request() || e.is_body()) =>
+            {
+                None
+            }
+            Some(ChefError::TimeoutError(TimeoutKind::Connect, _))
+            | Some(ChefError::TimeoutError(TimeoutKind::Read, _))
+                if self.retry_connection_errors =>
+            {
+                None
+            }
+            Some(e) => match e.server_error() {
+                Some(server) if self.statuses.contains(&server.status) => {
+                    retry_after(&server.headers)
+                }
+                _ => return None,
+            },
+            None => return None,
+        };
+
+        let delay = match retry_after {
+            Some(delay) if self.respect_retry_after => delay,
+            _ => self.backoff(attempt),
+        };
+        Some(delay.min(self.max_backoff))
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        if self.jitter && backoff > Duration::from_millis(1) {
+            let millis = backoff.as_millis() as u64;
+            Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Parse a `Retry-After` header, which holds either a number of seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ServerError;
+    use reqwest::header::HeaderValue;
+
+    fn server_error(status: u16, headers: HeaderMap) -> Error {
+        ChefError::from(ServerError::new(status, "get", "/nodes", headers, b"")).into()
+    }
+
+    #[test]
+    fn test_default_policy_never_retries() {
+        let err = server_error(503, HeaderMap::new());
+        assert_eq!(RetryPolicy::default().retry_delay(1, "get", &err), None)
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RetryPolicy::new(10)
+            .jitter(false)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500));
+        let err = server_error(502, HeaderMap::new());
+        let delays: Vec<_> = (1..6)
+            .map(|n| policy.retry_delay(n, "get", &err).unwrap().as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500])
+    }
+
+    #[test]
+    fn test_jittered_backoff() {
+        let policy = RetryPolicy::new(3).initial_backoff(Duration::from_millis(100));
+        let delay = policy.backoff(2).as_millis();
+        assert!((100..=200).contains(&delay))
+    }
+
+    #[test]
+    fn test_retryable_requests() {
+        let policy = RetryPolicy::new(3);
+        let unavailable = server_error(503, HeaderMap::new());
+        assert!(policy.retry_delay(1, "get", &unavailable).is_some());
+        assert!(policy.retry_delay(3, "get", &unavailable).is_none());
+        assert!(policy.retry_delay(1, "post", &unavailable).is_none());
+        assert!(policy
+            .retry_delay(1, "get", &server_error(404, HeaderMap::new()))
+            .is_none())
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        let err = server_error(503, headers);
+        assert_eq!(
+            RetryPolicy::new(2).retry_delay(1, "get", &err),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            RetryPolicy::new(2)
+                .max_backoff(Duration::from_secs(5))
+                .retry_delay(1, "get", &err),
+            Some(Duration::from_secs(5))
+        )
+    }
+}
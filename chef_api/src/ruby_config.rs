@@ -0,0 +1,597 @@
+//! Evaluates the static subset of Ruby used by most `knife.rb`, `config.rb` and `client.rb`
+//! files.
+//!
+//! Settings are read from method calls such as `node_name "barney"` or
+//! `chef_server_url("https://chef.local/organizations/bedrock")`. Values may be string,
+//! symbol, number or boolean literals, local variables assigned earlier in the file, and a few
+//! common helpers: `File.dirname(__FILE__)`, `__dir__`, `File.join`, `File.expand_path`,
+//! `Dir.home` and `ENV`. Settings this module does not use are skipped, but a setting it does
+//! use whose value can not be evaluated, or which is set conditionally, is an error.
+
+use crate::errors::ChefError;
+use failure::Error;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// The settings read from a Ruby configuration file, and the values they were set to.
+pub(crate) type Settings = HashMap<String, String>;
+
+/// Keywords that open a block closed by `end`.
+const BLOCK_KEYWORDS: &[&str] = &[
+    "if", "unless", "case", "while", "until", "begin", "def", "class", "module", "for",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Val {
+    Str(String),
+    Bool(bool),
+    Nil,
+    Array(Vec<Val>),
+}
+
+/// Evaluate `source`, read from `path`, returning the values of each of `keys` that it sets.
+pub(crate) fn parse(source: &str, path: &Path, keys: &[&str]) -> Result<Settings, Error> {
+    Evaluator {
+        path,
+        keys,
+        locals: HashMap::new(),
+        settings: HashMap::new(),
+        depth: 0,
+    }
+    .run(source)
+}
+
+struct Evaluator<'a> {
+    path: &'a Path,
+    keys: &'a [&'a str],
+    locals: HashMap<String, Result<Val, String>>,
+    settings: Settings,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn run(mut self, source: &str) -> Result<Settings, Error> {
+        for (n, line) in source.lines().enumerate() {
+            let line = strip_comment(line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            self.statement(line).map_err(|message| {
+                ChefError::RubyConfigError(self.path.display().to_string(), n + 1, message)
+            })?;
+        }
+        Ok(self.settings)
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), String> {
+        let first = line
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or("");
+        let key = self.keys.iter().find(|&&k| k == first).copied();
+
+        let opens_block = BLOCK_KEYWORDS.contains(&first)
+            || line.ends_with(" do")
+            || line.contains(" do |")
+            || line == "do";
+        if first == "end" {
+            self.depth = self.depth.saturating_sub(1);
+            return Ok(());
+        }
+        if opens_block {
+            self.depth += 1;
+        }
+
+        let mut parser = Parser::new(line);
+        let ident = parser.ident();
+
+        // `name = value` assigns a local variable, even if `name` is also a setting.
+        if let Some(ref ident) = ident {
+            let mut assignment = parser.clone();
+            assignment.skip_whitespace();
+            if assignment.eat('=') && !assignment.peek_is('=') && !assignment.peek_is('~') {
+                let value = assignment.expression(self).and_then(|value| {
+                    assignment.skip_whitespace();
+                    match assignment.rest() {
+                        "" => Ok(value),
+                        rest => Err(format!("unexpected `{}`", rest)),
+                    }
+                });
+                self.locals.insert(ident.clone(), value);
+                return Ok(());
+            }
+        }
+
+        let key = match key {
+            Some(key) if ident.as_deref() == Some(key) => key,
+            _ => return self.skip(line),
+        };
+        if self.depth > 0 || opens_block {
+            return Err(format!(
+                "`{}` is set conditionally, which can't be evaluated",
+                key
+            ));
+        }
+
+        parser.skip_whitespace();
+        let parenthesised = parser.eat('(');
+        let value = parser
+            .expression(self)
+            .map_err(|e| format!("can't evaluate the value of `{}`: {}", key, e))?;
+        parser.skip_whitespace();
+        if parenthesised && !parser.eat(')') {
+            return Err(format!("expected `)` after the value of `{}`", key));
+        }
+        parser.skip_whitespace();
+        if !parser.rest().is_empty() {
+            return Err(format!(
+                "can't evaluate `{}` after the value of `{}`",
+                parser.rest(),
+                key
+            ));
+        }
+
+        match value {
+            Val::Str(value) => {
+                self.settings.insert(key.into(), value);
+            }
+            Val::Bool(value) => {
+                self.settings.insert(key.into(), value.to_string());
+            }
+            Val::Nil => {
+                self.settings.remove(key);
+            }
+            Val::Array(_) => return Err(format!("`{}` must not be a list", key)),
+        }
+        Ok(())
+    }
+
+    /// Skip a statement that does not set one of the settings we read, unless it appears to
+    /// set one in some other way.
+    fn skip(&self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .collect();
+        match self.keys.iter().find(|key| words.contains(key)) {
+            Some(key) => Err(format!(
+                "`{}` is set by Ruby that can't be evaluated: `{}`",
+                key, line
+            )),
+            None => {
+                debug!("Skipping {:?} in {}", line, self.path.display());
+                Ok(())
+            }
+        }
+    }
+
+    fn file(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn dir(&self) -> String {
+        match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+            _ => String::from("."),
+        }
+    }
+}
+
+/// Remove a trailing comment, ignoring `#` within strings and interpolation.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (_, _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some('"'), '#') if chars.peek().map(|&(_, c)| c) == Some('{') => {
+                // Interpolations rarely contain quotes; treat them as part of the string.
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+#[derive(Clone)]
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn new(src: &'s str) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_is(&self, c: char) -> bool {
+        self.peek() == Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek_is(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.pos += len;
+        Some(rest[..len].into())
+    }
+
+    fn expression(&mut self, ev: &Evaluator) -> Result<Val, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                self.double_quoted(ev).map(Val::Str)
+            }
+            Some('\'') => {
+                self.pos += 1;
+                self.single_quoted().map(Val::Str)
+            }
+            Some(':') => {
+                self.pos += 1;
+                match self.ident() {
+                    Some(symbol) => Ok(Val::Str(symbol)),
+                    None => Err(String::from("expected a symbol after `:`")),
+                }
+            }
+            Some('[') => {
+                self.pos += 1;
+                self.list(ev, ']').map(Val::Array)
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let rest = self.rest();
+                let len = rest[1..]
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+                    .map(|l| l + 1)
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Ok(Val::Str(rest[..len].replace('_', "")))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let ident = self.ident().unwrap_or_default();
+                self.identifier(ev, &ident)
+            }
+            Some(_) => Err(format!("can't evaluate `{}`", self.rest())),
+            None => Err(String::from("a value is missing")),
+        }
+    }
+
+    fn identifier(&mut self, ev: &Evaluator, ident: &str) -> Result<Val, String> {
+        match ident {
+            "true" => return Ok(Val::Bool(true)),
+            "false" => return Ok(Val::Bool(false)),
+            "nil" => return Ok(Val::Nil),
+            "__FILE__" => return Ok(Val::Str(ev.file())),
+            "__dir__" => return Ok(Val::Str(ev.dir())),
+            "ENV" => return self.env(ev),
+            _ => (),
+        }
+
+        if ident.starts_with(char::is_uppercase) {
+            if !self.eat('.') {
+                return Err(format!("can't evaluate the constant `{}`", ident));
+            }
+            let method = self.ident().unwrap_or_default();
+            let args = if self.eat('(') {
+                self.list(ev, ')')?
+            } else {
+                vec![]
+            };
+            return call(ev, ident, &method, args);
+        }
+
+        match ev.locals.get(ident) {
+            Some(Ok(value)) => Ok(value.clone()),
+            Some(Err(e)) => Err(format!("the local variable `{}` is dynamic: {}", ident, e)),
+            None => Err(format!(
+                "`{}` is not a local variable assigned earlier in the file",
+                ident
+            )),
+        }
+    }
+
+    /// `ENV["NAME"]`, which is nil when unset, or `ENV.fetch("NAME", default)`.
+    fn env(&mut self, ev: &Evaluator) -> Result<Val, String> {
+        let (name, default) = if self.eat('[') {
+            let name = self.expression(ev)?;
+            self.skip_whitespace();
+            if !self.eat(']') {
+                return Err(String::from("expected `]` after `ENV[`"));
+            }
+            (name, Some(Val::Nil))
+        } else if self.rest().starts_with(".fetch(") {
+            self.pos += ".fetch(".len();
+            let mut args = self.list(ev, ')')?.into_iter();
+            (args.next().unwrap_or(Val::Nil), args.next())
+        } else {
+            return Err(String::from(
+                "only `ENV[...]` and `ENV.fetch` can be evaluated",
+            ));
+        };
+        match name {
+            Val::Str(name) => match env::var(&name) {
+                Ok(value) => Ok(Val::Str(value)),
+                Err(_) => {
+                    default.ok_or_else(|| format!("the environment variable {} is not set", name))
+                }
+            },
+            _ => Err(String::from("environment variable names must be strings")),
+        }
+    }
+
+    fn list(&mut self, ev: &Evaluator, close: char) -> Result<Vec<Val>, String> {
+        let mut items = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.eat(close) {
+                return Ok(items);
+            }
+            items.push(self.expression(ev)?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                self.skip_whitespace();
+                return if self.eat(close) {
+                    Ok(items)
+                } else {
+                    Err(format!("expected `,` or `{}` in `{}`", close, self.src))
+                };
+            }
+        }
+    }
+
+    fn single_quoted(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\'' => {
+                    self.pos += i + 1;
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) if c == '\'' || c == '\\' => value.push(c),
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c)
+                    }
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        Err(String::from("unterminated string"))
+    }
+
+    fn double_quoted(&mut self, ev: &Evaluator) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| String::from("unterminated string"))?;
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let c = self
+                        .peek()
+                        .ok_or_else(|| String::from("unterminated string"))?;
+                    self.pos += c.len_utf8();
+                    match c {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        c => value.push(c),
+                    }
+                }
+                '#' if self.eat('{') => {
+                    let inner = self.expression(ev)?;
+                    self.skip_whitespace();
+                    if !self.eat('}') {
+                        return Err(String::from(
+                            "only simple expressions can be interpolated into strings",
+                        ));
+                    }
+                    match inner {
+                        Val::Str(s) => value.push_str(&s),
+                        Val::Bool(b) => value.push_str(&b.to_string()),
+                        Val::Nil => (),
+                        Val::Array(_) => {
+                            return Err(String::from("lists can't be interpolated"));
+                        }
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+    }
+}
+
+/// Evaluate one of the supported `File` and `Dir` methods.
+fn call(ev: &Evaluator, receiver: &str, method: &str, args: Vec<Val>) -> Result<Val, String> {
+    let strings = || {
+        args.iter()
+            .map(|arg| match arg {
+                Val::Str(s) => Ok(s.clone()),
+                _ => Err(format!("`{}.{}` expects strings", receiver, method)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    match (receiver, method) {
+        ("File", "join") => {
+            let parts = strings()?;
+            let mut path = PathBuf::new();
+            for part in parts {
+                path.push(part.trim_start_matches('/'));
+            }
+            let path = path.display().to_string();
+            match args.first() {
+                Some(Val::Str(first)) if first.starts_with('/') => {
+                    Ok(Val::Str(format!("/{}", path.trim_start_matches('/'))))
+                }
+                _ => Ok(Val::Str(path)),
+            }
+        }
+        ("File", "dirname") => match strings()?.first() {
+            Some(path) => Ok(Val::Str(match Path::new(path).parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+                _ => String::from("."),
+            })),
+            None => Err(String::from("`File.dirname` expects a path")),
+        },
+        ("File", "expand_path") => {
+            let args = strings()?;
+            let path = args
+                .first()
+                .ok_or_else(|| String::from("`File.expand_path` expects a path"))?;
+            let path = match path.strip_prefix('~') {
+                Some(rest) => home_dir()?.join(rest.trim_start_matches('/')),
+                None => PathBuf::from(path),
+            };
+            let base = args.get(1).cloned().unwrap_or_else(|| ev.dir());
+            Ok(Val::Str(normalize(&Path::new(&base).join(path))))
+        }
+        ("Dir", "home") => Ok(Val::Str(home_dir()?.display().to_string())),
+        _ => Err(format!("can't evaluate `{}.{}`", receiver, method)),
+    }
+}
+
+fn home_dir() -> Result<PathBuf, String> {
+    dirs::home_dir().ok_or_else(|| String::from("the home directory is unknown"))
+}
+
+/// Resolve `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> String {
+    use std::path::Component;
+
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normal.pop();
+            }
+            c => normal.push(c.as_os_str()),
+        }
+    }
+    normal.display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: &[&str] = &[
+        "chef_server_url",
+        "node_name",
+        "client_key",
+        "ssl_verify_mode",
+    ];
+
+    fn parse_str(source: &str) -> Result<Settings, Error> {
+        parse(source, Path::new("/home/barney/.chef/knife.rb"), KEYS)
+    }
+
+    #[test]
+    fn test_static_settings() {
+        let settings = parse_str(
+            r##"
+            # See https://docs.chef.io/config_rb/
+            current_dir = File.dirname(__FILE__)
+            log_level                :info
+            log_location             STDOUT
+            node_name                "barney" # the user name
+            client_key               "#{current_dir}/barney.pem"
+            chef_server_url          'https://chef.local/organizations/bedrock'
+            ssl_verify_mode(:verify_none)
+            cookbook_path            ["#{current_dir}/../cookbooks"]
+            knife[:editor] = "vim"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(settings["node_name"], "barney");
+        assert_eq!(settings["client_key"], "/home/barney/.chef/barney.pem");
+        assert_eq!(
+            settings["chef_server_url"],
+            "https://chef.local/organizations/bedrock"
+        );
+        assert_eq!(settings["ssl_verify_mode"], "verify_none");
+    }
+
+    #[test]
+    fn test_file_helpers() {
+        let settings = parse_str(
+            r#"
+            client_key File.join(__dir__, "keys", 'barney.pem')
+            node_name File.expand_path("../barney", __dir__)
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings["client_key"], "/home/barney/.chef/keys/barney.pem");
+        assert_eq!(settings["node_name"], "/home/barney/barney");
+    }
+
+    #[test]
+    fn test_environment() {
+        env::set_var("RUBY_CONFIG_TEST_NODE", "fred");
+        let settings = parse_str(
+            r#"
+            node_name ENV["RUBY_CONFIG_TEST_NODE"]
+            client_key ENV.fetch("RUBY_CONFIG_TEST_UNSET", "/etc/chef/client.pem")
+            "#,
+        )
+        .unwrap();
+        assert_eq!(settings["node_name"], "fred");
+        assert_eq!(settings["client_key"], "/etc/chef/client.pem");
+    }
+
+    #[test]
+    fn test_dynamic_ruby() {
+        let err = parse_str("node_name Socket.gethostname").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Can't evaluate line 1 of /home/barney/.chef/knife.rb: can't evaluate the value \
+             of `node_name`: can't evaluate `Socket.gethostname`"
+        );
+
+        assert!(parse_str("node_name `hostname`.chomp").is_err());
+        assert!(parse_str("node_name \"barney\" if ENV['CI']").is_err());
+        assert!(parse_str("if ENV['CI']\n  node_name 'ci'\nend").is_err());
+        assert!(parse_str("user = Etc.getlogin\nnode_name user").is_err());
+        assert!(parse_str("Chef::Config[:node_name] = 'barney'").is_err());
+
+        // Dynamic Ruby is fine when it doesn't affect the settings we need.
+        assert!(parse_str("if ENV['CI']\n  log_level :debug\nend\nnode_name 'barney'").is_ok());
+    }
+}
@@ -0,0 +1,87 @@
+//! Helpers for tests that make requests to a local mock Chef Server.
+
+use crate::api_client::ApiClient;
+use crate::credentials::Config;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Create an `ApiClient` for `url`, signing as `spec-user` with the fixture key.
+pub(crate) fn api_client(url: &str) -> ApiClient {
+    let credentials = format!(
+        "[default]\nclient_name = 'spec-user'\n\
+         client_key = '{}/fixtures/spec-user.pem'\nchef_server_url = '{}'",
+        env!("CARGO_MANIFEST_DIR"),
+        url
+    );
+    ApiClient::new(Config::from_str(&credentials, "default").unwrap()).unwrap()
+}
+
+/// Serve one request on a local port for each of `responses`, in turn, passing the text of
+/// each request back through the returned channel.
+pub(crate) fn serve(responses: Vec<String>) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/organizations/bedrock",
+        listener.local_addr().unwrap()
+    );
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 1024];
+            while !is_complete(&request) {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            let _ = tx.send(String::from_utf8_lossy(&request).into_owned());
+        }
+    });
+    (url, rx)
+}
+
+/// Serve a single request on a local port, answering with `status` and no body.
+pub(crate) fn respond_once(status: &str) -> String {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    serve(vec![response]).0
+}
+
+/// A successful response with `body` as its JSON content.
+pub(crate) fn json_response(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// Returns the body of a request captured by `serve`.
+pub(crate) fn request_body(request: &str) -> &str {
+    request.split_once("\r\n\r\n").map_or("", |(_, body)| body)
+}
+
+/// Returns true once the headers and the whole body of a request have been read.
+fn is_complete(request: &[u8]) -> bool {
+    let request = String::from_utf8_lossy(request);
+    let (headers, body) = match request.find("\r\n\r\n") {
+        Some(i) => (&request[..i], &request[i + 4..]),
+        None => return false,
+    };
+    let length = headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+    body.len() >= length
+}
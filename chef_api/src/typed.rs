@@ -0,0 +1,137 @@
+use crate::api_client::{Execute, ExecuteAsync, TypedResponseFuture};
+
+use failure::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A request that knows the type of its response.
+///
+/// `TypedRequest` wraps one of the request builders, deserializing each response into a `T`
+/// rather than returning raw JSON. Use `request` to reach the wrapped builder, for example to
+/// set query parameters or timeouts.
+///
+/// ```rust,no_run
+/// # use chef_api::api_client::ApiClient;
+/// # use chef_api::typed::TypedRequest;
+/// # use std::collections::HashMap;
+/// # let api = ApiClient::from_credentials(None).unwrap();
+/// let nodes: TypedRequest<_, HashMap<String, String>> = TypedRequest::new(api.nodes());
+/// for (name, url) in nodes.get().unwrap() {
+///     println!("{} is at {}", name, url);
+/// }
+/// ```
+pub struct TypedRequest<Q, T> {
+    request: Q,
+    response: PhantomData<fn() -> T>,
+}
+
+impl<Q, T> TypedRequest<Q, T>
+where
+    Q: Execute + ExecuteAsync,
+    T: DeserializeOwned,
+{
+    /// Wrap `request`, whose responses will be deserialized into a `T`.
+    pub fn new(request: Q) -> Self {
+        Self {
+            request,
+            response: PhantomData,
+        }
+    }
+
+    /// The wrapped request builder.
+    pub fn request(&mut self) -> &mut Q {
+        &mut self.request
+    }
+
+    /// Unwrap the request builder, discarding the response type.
+    pub fn into_inner(self) -> Q {
+        self.request
+    }
+
+    /// Make an HTTP `get` request.
+    pub fn get(&self) -> Result<T, Error> {
+        self.request.get_as()
+    }
+
+    /// Make an HTTP `delete` request.
+    pub fn delete(&self) -> Result<T, Error> {
+        self.request.delete_as()
+    }
+
+    /// Check whether the object exists, using a `head` request.
+    pub fn exists(&self) -> Result<bool, Error> {
+        self.request.exists()
+    }
+
+    /// Make an HTTP `post` request, accepting a `B` which can be converted into JSON.
+    pub fn post<B: Serialize>(&self, body: &B) -> Result<T, Error> {
+        self.request.post_as(body)
+    }
+
+    /// Make an HTTP `put` request, accepting a `B` which can be converted into JSON.
+    pub fn put<B: Serialize>(&self, body: &B) -> Result<T, Error> {
+        self.request.put_as(body)
+    }
+}
+
+impl<Q, T> TypedRequest<Q, T>
+where
+    Q: Execute + ExecuteAsync,
+    T: DeserializeOwned + Send + 'static,
+{
+    /// Make an asynchronous HTTP `get` request.
+    pub fn get_async(&self) -> TypedResponseFuture<T> {
+        self.request.get_as_async()
+    }
+
+    /// Make an asynchronous HTTP `delete` request.
+    pub fn delete_async(&self) -> TypedResponseFuture<T> {
+        self.request.delete_as_async()
+    }
+
+    /// Make an asynchronous HTTP `post` request.
+    pub fn post_async<B: Serialize>(&self, body: &B) -> TypedResponseFuture<T> {
+        self.request.post_as_async(body)
+    }
+
+    /// Make an asynchronous HTTP `put` request.
+    pub fn put_async<B: Serialize>(&self, body: &B) -> TypedResponseFuture<T> {
+        self.request.put_as_async(body)
+    }
+}
+
+impl<Q: Clone, T> Clone for TypedRequest<Q, T> {
+    fn clone(&self) -> Self {
+        Self {
+            request: self.request.clone(),
+            response: PhantomData,
+        }
+    }
+}
+
+impl<Q: fmt::Debug, T> fmt::Debug for TypedRequest<Q, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedRequest")
+            .field("request", &self.request)
+            .field("response", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::NodesQuery;
+    use crate::test_support::api_client;
+    use serde_json::Value;
+
+    #[test]
+    fn test_request_builder_is_reachable() {
+        let api = api_client("https://localhost/organizations/clownco");
+        let mut nodes: TypedRequest<NodesQuery, Value> = TypedRequest::new(api.nodes());
+        nodes.request().node("web1");
+        assert_eq!(nodes.into_inner().path, "/organizations/clownco/nodes/web1")
+    }
+}
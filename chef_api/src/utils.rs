@@ -1,6 +1,17 @@
 use crate::errors::ChefError;
+use chrono::{DateTime, Duration, Utc};
 use failure::Error;
+use reqwest::header::{HeaderMap, DATE};
 use serde_json::Value;
+use url::form_urlencoded;
+
+/// Returns how far the clock of the server that sent `headers` is ahead of the local clock,
+/// according to its `Date` header.
+pub fn clock_offset(headers: &HeaderMap) -> Option<Duration> {
+    let date = headers.get(DATE)?.to_str().ok()?;
+    let date = DateTime::parse_from_rfc2822(date).ok()?;
+    Some(date.with_timezone(&Utc) - Utc::now())
+}
 
 pub fn expand_string(val: &Option<String>) -> String {
     match *val {
@@ -12,7 +23,7 @@ pub fn expand_string(val: &Option<String>) -> String {
 /// Remove duplicate and trailing slashes from a path
 pub fn squeeze_path(pth: &str) -> String {
     let mut st = String::new();
-    for p in pth.split('/').filter(|&x| x != "") {
+    for p in pth.split('/').filter(|&x| !x.is_empty()) {
         st.push('/');
         st.push_str(p)
     }
@@ -23,6 +34,27 @@ pub fn squeeze_path(pth: &str) -> String {
     }
 }
 
+/// Strip any query string from a path, as the Chef Server does not include it when verifying
+/// a request's signature.
+pub fn strip_query(pth: &str) -> &str {
+    match pth.find('?') {
+        Some(idx) => &pth[..idx],
+        None => pth,
+    }
+}
+
+/// Build a URL encoded query string, including the leading `?`, from a list of parameters.
+/// Returns an empty string when there are no parameters.
+pub fn encode_query(params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    format!("?{}", query)
+}
+
 pub fn add_path_element(path: String, elem: &str) -> String {
     let mut path = path;
     path.push('/');
@@ -48,7 +80,9 @@ pub fn decode_list(data: &Value) -> Result<Vec<String>, Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::squeeze_path;
+    use super::{clock_offset, encode_query, squeeze_path, strip_query};
+    use chrono::{Duration, Utc};
+    use reqwest::header::{HeaderMap, HeaderValue, DATE};
 
     #[test]
     fn test_squeeze_path() {
@@ -73,4 +107,40 @@ mod tests {
         let path = String::from("/any/given/path/");
         assert_eq!("/any/given/path", squeeze_path(&path))
     }
+
+    #[test]
+    fn test_strip_query() {
+        assert_eq!("/search/node", strip_query("/search/node?q=*:*&rows=10"));
+        assert_eq!("/search/node", strip_query("/search/node"))
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let params = vec![
+            (
+                String::from("q"),
+                String::from("name:web* AND chef_environment:_default"),
+            ),
+            (String::from("rows"), String::from("10")),
+        ];
+        assert_eq!(
+            "?q=name%3Aweb*+AND+chef_environment%3A_default&rows=10",
+            encode_query(&params)
+        )
+    }
+
+    #[test]
+    fn test_encode_empty_query() {
+        assert_eq!("", encode_query(&[]))
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(clock_offset(&headers), None);
+        let date = (Utc::now() + Duration::minutes(20)).to_rfc2822();
+        headers.insert(DATE, HeaderValue::from_str(&date).unwrap());
+        let offset = clock_offset(&headers).unwrap();
+        assert!(offset > Duration::minutes(19) && offset <= Duration::minutes(20))
+    }
 }
@@ -18,11 +18,18 @@ pub fn main() {
     info!("{:?}", nodes);
     match nodes {
         Ok(nodes) => {
-            let nodes: NodeList = nodes.into();
-            for n in nodes {
-                let node: Node = Node::try_from(client.nodes().node(&n).get().unwrap()).unwrap();
-                println!("{}", node.name.clone().unwrap());
-                info!("{:?}", node);
+            let nodes: Vec<String> = NodeList::from(nodes).collect();
+            for (name, node) in client
+                .fetch_many(&BulkEndpoint::Nodes, &nodes, DEFAULT_CONCURRENCY)
+                .unwrap()
+            {
+                match node.and_then(Node::try_from) {
+                    Ok(node) => {
+                        println!("{}", node.name.clone().unwrap());
+                        info!("{:?}", node);
+                    }
+                    Err(e) => println!("{}: {}", name, e),
+                }
             }
         }
         Err(e) => println!("{}", e),
@@ -9,3 +9,4 @@ extern crate chef_api;
 extern crate failure;
 
 pub mod models;
+pub mod requests;
@@ -7,7 +7,7 @@ chef_json_type!(CookbookVersionChefType, "cookbook_version");
 #[serde(default)]
 pub struct CookbookVersion {
     pub cookbook_name: String,
-    chef_type: String,
+    chef_type: CookbookVersionChefType,
     pub version: String,
     pub attributes: Vec<HashMap<String, Value>>,
     pub metadata: HashMap<String, Value>,
@@ -18,7 +18,7 @@ pub struct CookbookVersion {
     root_files: Vec<HashMap<String, Value>>,
     #[serde(rename = "frozen?")]
     pub frozen: bool,
-    json_class: String,
+    json_class: CookbookVersionJsonClass,
 }
 
 model_impl!(CookbookVersion);
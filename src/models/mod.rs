@@ -15,20 +15,24 @@ macro_rules! model_list {
     ($id:ident) => {
         #[derive(Debug)]
         pub struct $id {
-            count: usize,
             items: Vec<String>,
         }
 
         impl From<Value> for $id {
             fn from(list: Value) -> Self {
+                decode_list(&list).map(|list| Self { items: list }).unwrap()
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $id {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                let list = Value::deserialize(deserializer)?;
                 decode_list(&list)
-                    .and_then(|list| {
-                        Ok(Self {
-                            items: list,
-                            count: 0,
-                        })
-                    })
-                    .unwrap()
+                    .map(|list| Self { items: list })
+                    .map_err(::serde::de::Error::custom)
             }
         }
 
@@ -104,3 +108,22 @@ pub mod cookbook;
 pub use self::cookbook::*;
 pub mod client;
 pub use self::client::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_list() {
+        let list: NodeList = serde_json::from_str(
+            r#"{"web1": "https://chef.local/nodes/web1", "db1": "https://chef.local/nodes/db1"}"#,
+        )
+        .unwrap();
+        assert_eq!(list.collect::<Vec<_>>(), vec!["db1", "web1"])
+    }
+
+    #[test]
+    fn test_deserialize_list_requires_an_object() {
+        assert!(serde_json::from_str::<RoleList>(r#"["web"]"#).is_err())
+    }
+}
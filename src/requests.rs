@@ -0,0 +1,239 @@
+//! Requests that know which model they return.
+//!
+//! `TypedRequests` adds methods to `ApiClient` which build the usual `chef_api` requests, but
+//! deserialize the response into the matching model rather than returning raw JSON.
+//!
+//! ```rust,no_run
+//! use chef_api::api_client::ApiClient;
+//! use chef::requests::TypedRequests;
+//!
+//! # fn main() -> Result<(), failure::Error> {
+//! let client = ApiClient::from_credentials(None)?;
+//! for name in client.node_list().get()? {
+//!     let node = client.node(&name).get()?;
+//!     println!("{} runs {:?}", name, node.run_list);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::*;
+use chef_api::api_client::ApiClient;
+use chef_api::requests::*;
+use chef_api::typed::TypedRequest;
+
+/// Build requests whose responses are deserialized into models.
+pub trait TypedRequests {
+    /// Fetch, update or delete a single node.
+    fn node(&self, name: &str) -> TypedRequest<NodesQuery<'_>, Node>;
+
+    /// List the names of all nodes.
+    fn node_list(&self) -> TypedRequest<NodesQuery<'_>, NodeList>;
+
+    /// Fetch, update or delete a single role.
+    fn role(&self, name: &str) -> TypedRequest<RolesQuery<'_>, Role>;
+
+    /// List the names of all roles.
+    fn role_list(&self) -> TypedRequest<RolesQuery<'_>, RoleList>;
+
+    /// Fetch, update or delete a single environment.
+    fn environment(&self, name: &str) -> TypedRequest<EnvironmentsQuery<'_>, Environment>;
+
+    /// List the names of all environments.
+    fn environment_list(&self) -> TypedRequest<EnvironmentsQuery<'_>, EnvironmentList>;
+
+    /// Fetch, update or delete a single client.
+    fn client(&self, name: &str) -> TypedRequest<ClientsQuery<'_>, Client>;
+
+    /// List the names of all clients.
+    fn client_list(&self) -> TypedRequest<ClientsQuery<'_>, ClientList>;
+
+    /// List the names of all data bags.
+    fn data_bag_list(&self) -> TypedRequest<DataQuery<'_>, DataBagList>;
+
+    /// List the names of the items in a data bag.
+    fn data_bag_item_list(&self, data_bag: &str) -> TypedRequest<DataQuery<'_>, DataBagItemList>;
+
+    /// Fetch, update or delete a single data bag item.
+    fn data_bag_item(&self, data_bag: &str, item: &str)
+        -> TypedRequest<DataQuery<'_>, DataBagItem>;
+
+    /// List the names of all cookbooks.
+    fn cookbook_list(&self) -> TypedRequest<CookbooksQuery<'_>, CookbookVersionList>;
+
+    /// Fetch a single version of a cookbook; use `_latest` for the most recent version.
+    fn cookbook_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> TypedRequest<CookbooksQuery<'_>, CookbookVersion>;
+}
+
+impl TypedRequests for ApiClient {
+    fn node(&self, name: &str) -> TypedRequest<NodesQuery<'_>, Node> {
+        let mut request = self.nodes();
+        request.node(name);
+        TypedRequest::new(request)
+    }
+
+    fn node_list(&self) -> TypedRequest<NodesQuery<'_>, NodeList> {
+        TypedRequest::new(self.nodes())
+    }
+
+    fn role(&self, name: &str) -> TypedRequest<RolesQuery<'_>, Role> {
+        let mut request = self.roles();
+        request.role(name);
+        TypedRequest::new(request)
+    }
+
+    fn role_list(&self) -> TypedRequest<RolesQuery<'_>, RoleList> {
+        TypedRequest::new(self.roles())
+    }
+
+    fn environment(&self, name: &str) -> TypedRequest<EnvironmentsQuery<'_>, Environment> {
+        let mut request = self.environments();
+        request.environment(name);
+        TypedRequest::new(request)
+    }
+
+    fn environment_list(&self) -> TypedRequest<EnvironmentsQuery<'_>, EnvironmentList> {
+        TypedRequest::new(self.environments())
+    }
+
+    fn client(&self, name: &str) -> TypedRequest<ClientsQuery<'_>, Client> {
+        let mut request = self.clients();
+        request.client(name);
+        TypedRequest::new(request)
+    }
+
+    fn client_list(&self) -> TypedRequest<ClientsQuery<'_>, ClientList> {
+        TypedRequest::new(self.clients())
+    }
+
+    fn data_bag_list(&self) -> TypedRequest<DataQuery<'_>, DataBagList> {
+        TypedRequest::new(self.data())
+    }
+
+    fn data_bag_item_list(&self, data_bag: &str) -> TypedRequest<DataQuery<'_>, DataBagItemList> {
+        let mut request = self.data();
+        request.data_bag(data_bag);
+        TypedRequest::new(request)
+    }
+
+    fn data_bag_item(
+        &self,
+        data_bag: &str,
+        item: &str,
+    ) -> TypedRequest<DataQuery<'_>, DataBagItem> {
+        let mut request = self.data();
+        request.data_bag(data_bag).item(item);
+        TypedRequest::new(request)
+    }
+
+    fn cookbook_list(&self) -> TypedRequest<CookbooksQuery<'_>, CookbookVersionList> {
+        TypedRequest::new(self.cookbooks())
+    }
+
+    fn cookbook_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> TypedRequest<CookbooksQuery<'_>, CookbookVersion> {
+        let mut request = self.cookbooks();
+        request.cookbook(name).version(version);
+        TypedRequest::new(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chef_api::credentials::Config;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answer a single request on a local port with `body`, returning the URL of an
+    /// organization on it and a handle that yields the request line.
+    fn serve(body: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/organizations/bedrock",
+            listener.local_addr().unwrap()
+        );
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buf = [0; 1024];
+            while !is_complete(&request) {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            let request = String::from_utf8_lossy(&request);
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (url, handle)
+    }
+
+    /// Whether `request` holds the headers and as much of the body as its `Content-Length`
+    /// promises.
+    fn is_complete(request: &[u8]) -> bool {
+        let request = String::from_utf8_lossy(request);
+        let (headers, body) = match request.split_once("\r\n\r\n") {
+            Some(parts) => parts,
+            None => return false,
+        };
+        let length = headers
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+            .unwrap_or(0);
+        body.len() >= length
+    }
+
+    fn api_client(url: &str) -> ApiClient {
+        let credentials = format!(
+            "[default]\nclient_name = 'spec-user'\n\
+             client_key = '{}/chef_api/fixtures/spec-user.pem'\nchef_server_url = '{}'",
+            env!("CARGO_MANIFEST_DIR"),
+            url
+        );
+        ApiClient::new(Config::from_str(&credentials, "default").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_node() {
+        let (url, request) =
+            serve(r#"{"name": "web1", "chef_environment": "prod", "run_list": ["role[web]"]}"#);
+        let node = api_client(&url).node("web1").get().unwrap();
+        assert_eq!(
+            request.join().unwrap(),
+            "GET /organizations/bedrock/nodes/web1 HTTP/1.1"
+        );
+        assert_eq!(node.name.as_deref(), Some("web1"));
+        assert_eq!(node.chef_environment, "prod");
+        assert_eq!(node.run_list, vec!["role[web]"])
+    }
+
+    #[test]
+    fn test_node_list() {
+        let (url, request) = serve(r#"{"web1": "https://chef.local/nodes/web1"}"#);
+        let nodes = api_client(&url).node_list().get().unwrap();
+        assert_eq!(
+            request.join().unwrap(),
+            "GET /organizations/bedrock/nodes HTTP/1.1"
+        );
+        assert_eq!(nodes.collect::<Vec<_>>(), vec!["web1"])
+    }
+}